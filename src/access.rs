@@ -0,0 +1,93 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::selection::Row;
+use crate::table::AccessStats;
+
+pub(crate) struct Accesses {
+    reads: Vec<AtomicU64>,
+    last_read: Vec<AtomicU64>,
+}
+
+impl Clone for Accesses {
+    fn clone(&self) -> Self {
+        Self {
+            reads: copy(&self.reads),
+            last_read: copy(&self.last_read),
+        }
+    }
+}
+
+impl Accesses {
+    pub(crate) fn new(len: usize) -> Self {
+        let mut accesses = Self {
+            reads: Vec::with_capacity(len),
+            last_read: Vec::with_capacity(len),
+        };
+        for _ in 0..len {
+            accesses.inserted();
+        }
+        accesses
+    }
+
+    pub(crate) fn retained(&self, indexes: &[usize]) -> Self {
+        Self {
+            reads: indexes
+                .iter()
+                .map(|&index| copy_one(&self.reads[index]))
+                .collect(),
+            last_read: indexes
+                .iter()
+                .map(|&index| copy_one(&self.last_read[index]))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn inserted(&mut self) {
+        self.reads.push(AtomicU64::new(0));
+        self.last_read.push(AtomicU64::new(0));
+    }
+
+    pub(crate) fn replaced<T>(&self, row: Row<T>) {
+        if let Some(reads) = self.reads.get(row.as_index()) {
+            reads.store(0, Ordering::Relaxed);
+            self.last_read[row.as_index()].store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn read<T>(&self, row: Row<T>) {
+        if let Some(reads) = self.reads.get(row.as_index()) {
+            reads.fetch_add(1, Ordering::Relaxed);
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_micros() as u64);
+            self.last_read[row.as_index()].store(now, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn stats<T>(&self) -> Vec<(Row<T>, AccessStats)> {
+        self.reads
+            .iter()
+            .zip(&self.last_read)
+            .enumerate()
+            .map(|(index, (reads, last_read))| {
+                let last_read = last_read.load(Ordering::Relaxed);
+                let stats = AccessStats {
+                    reads: reads.load(Ordering::Relaxed),
+                    last_read: Some(last_read)
+                        .filter(|&micros| micros > 0)
+                        .map(|micros| UNIX_EPOCH + Duration::from_micros(micros)),
+                };
+                (unsafe { Row::from_index(index) }, stats)
+            })
+            .collect()
+    }
+}
+
+fn copy(values: &[AtomicU64]) -> Vec<AtomicU64> {
+    values.iter().map(copy_one).collect()
+}
+
+fn copy_one(value: &AtomicU64) -> AtomicU64 {
+    AtomicU64::new(value.load(Ordering::Relaxed))
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+use std::ops::AddAssign;
+
+use crate::index::DiscreteIndex;
+use crate::selection::Selection;
+use crate::table::{Selectable, Table};
+
+pub trait Aggregator<T> {
+    type State;
+    type Output;
+
+    fn start(&self) -> Self::State;
+    fn update(&self, state: &mut Self::State, item: &T);
+    fn finish(&self, state: Self::State) -> Self::Output;
+}
+
+// groups
+
+pub struct Groups<'query, T, V, S>
+where
+    T: Selectable,
+    V: Eq + Hash,
+{
+    table: &'query Table<T>,
+    selection: &'query Selection<T>,
+    index: &'query DiscreteIndex<T, V, S>,
+}
+
+impl<'query, T, V, S> Groups<'query, T, V, S>
+where
+    T: Selectable,
+    V: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    pub(crate) fn new(
+        table: &'query Table<T>,
+        selection: &'query Selection<T>,
+        index: &'query DiscreteIndex<T, V, S>,
+    ) -> Self {
+        Self {
+            table,
+            selection,
+            index,
+        }
+    }
+
+    pub fn aggregate<A>(&self, aggregator: A) -> HashMap<V, A::Output>
+    where
+        A: Aggregator<T>,
+    {
+        let mut states: HashMap<&V, A::State> = HashMap::new();
+        for row in self.selection.rows() {
+            let item = self.table.borrow_row(row);
+            let state = states
+                .entry(self.index.key(item))
+                .or_insert_with(|| aggregator.start());
+            aggregator.update(state, item);
+        }
+        states
+            .into_iter()
+            .map(|(key, state)| (key.clone(), aggregator.finish(state)))
+            .collect()
+    }
+}
+
+// count
+
+pub struct Count;
+
+pub fn count() -> Count {
+    Count
+}
+
+impl<T> Aggregator<T> for Count {
+    type State = u64;
+    type Output = u64;
+
+    fn start(&self) -> u64 {
+        0
+    }
+
+    fn update(&self, state: &mut u64, _item: &T) {
+        *state += 1;
+    }
+
+    fn finish(&self, state: u64) -> u64 {
+        state
+    }
+}
+
+// sum
+
+pub struct Sum<F>(F);
+
+pub fn sum<F>(value: F) -> Sum<F> {
+    Sum(value)
+}
+
+impl<T, V, F> Aggregator<T> for Sum<F>
+where
+    F: Fn(&T) -> V,
+    V: Default + AddAssign,
+{
+    type State = V;
+    type Output = V;
+
+    fn start(&self) -> V {
+        V::default()
+    }
+
+    fn update(&self, state: &mut V, item: &T) {
+        *state += (self.0)(item);
+    }
+
+    fn finish(&self, state: V) -> V {
+        state
+    }
+}
+
+// min & max
+
+pub struct Min<F>(F);
+
+pub fn min<F>(value: F) -> Min<F> {
+    Min(value)
+}
+
+impl<T, V, F> Aggregator<T> for Min<F>
+where
+    F: Fn(&T) -> V,
+    V: Ord,
+{
+    type State = Option<V>;
+    type Output = Option<V>;
+
+    fn start(&self) -> Option<V> {
+        None
+    }
+
+    fn update(&self, state: &mut Option<V>, item: &T) {
+        let value = (self.0)(item);
+        match state {
+            Some(current) if *current <= value => {}
+            _ => *state = Some(value),
+        }
+    }
+
+    fn finish(&self, state: Option<V>) -> Option<V> {
+        state
+    }
+}
+
+pub struct Max<F>(F);
+
+pub fn max<F>(value: F) -> Max<F> {
+    Max(value)
+}
+
+impl<T, V, F> Aggregator<T> for Max<F>
+where
+    F: Fn(&T) -> V,
+    V: Ord,
+{
+    type State = Option<V>;
+    type Output = Option<V>;
+
+    fn start(&self) -> Option<V> {
+        None
+    }
+
+    fn update(&self, state: &mut Option<V>, item: &T) {
+        let value = (self.0)(item);
+        match state {
+            Some(current) if *current >= value => {}
+            _ => *state = Some(value),
+        }
+    }
+
+    fn finish(&self, state: Option<V>) -> Option<V> {
+        state
+    }
+}
+
+// combinations
+
+macro_rules! aggregator_tuple {
+    ($($name:ident $index:tt),+) => {
+        impl<T, $($name),+> Aggregator<T> for ($($name,)+)
+        where
+            $($name: Aggregator<T>),+
+        {
+            type State = ($($name::State,)+);
+            type Output = ($($name::Output,)+);
+
+            fn start(&self) -> Self::State {
+                ($(self.$index.start(),)+)
+            }
+
+            fn update(&self, state: &mut Self::State, item: &T) {
+                $(self.$index.update(&mut state.$index, item);)+
+            }
+
+            fn finish(&self, state: Self::State) -> Self::Output {
+                ($(self.$index.finish(state.$index),)+)
+            }
+        }
+    };
+}
+
+aggregator_tuple!(A 0);
+aggregator_tuple!(A 0, B 1);
+aggregator_tuple!(A 0, B 1, C 2);
+aggregator_tuple!(A 0, B 1, C 2, D 3);
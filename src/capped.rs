@@ -0,0 +1,152 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::selection::Row;
+use crate::table::{Eviction, Indexer};
+
+pub(crate) struct Capped {
+    max_rows: usize,
+    eviction: Eviction,
+    clock: AtomicU64,
+    last_used: Vec<AtomicU64>,
+    // one (tick, row) entry per row, oldest first; touches only bump
+    // last_used, so an entry may be stale and is refreshed when popped
+    recency: BinaryHeap<Reverse<(u64, usize)>>,
+    replaced: Vec<u64>,
+    oldest: usize,
+}
+
+impl Clone for Capped {
+    fn clone(&self) -> Self {
+        Self {
+            max_rows: self.max_rows,
+            eviction: self.eviction,
+            clock: AtomicU64::new(self.clock.load(Ordering::Relaxed)),
+            last_used: self
+                .last_used
+                .iter()
+                .map(|tick| AtomicU64::new(tick.load(Ordering::Relaxed)))
+                .collect(),
+            recency: self.recency.clone(),
+            replaced: self.replaced.clone(),
+            oldest: self.oldest,
+        }
+    }
+}
+
+impl Capped {
+    pub(crate) fn new(max_rows: usize, eviction: Eviction, len: usize) -> Self {
+        let mut capped = Self {
+            max_rows,
+            eviction,
+            clock: AtomicU64::new(0),
+            last_used: Vec::with_capacity(len),
+            recency: BinaryHeap::new(),
+            replaced: Vec::with_capacity(len),
+            oldest: 0,
+        };
+        for _ in 0..len {
+            capped.inserted();
+        }
+        capped
+    }
+
+    pub(crate) fn retained(&self, indexes: &[usize]) -> Self {
+        let mut capped = Self {
+            max_rows: self.max_rows,
+            eviction: self.eviction,
+            clock: AtomicU64::new(self.clock.load(Ordering::Relaxed)),
+            last_used: Vec::with_capacity(indexes.len()),
+            recency: BinaryHeap::new(),
+            replaced: vec![0; indexes.len()],
+            oldest: 0,
+        };
+        for (index, &old) in indexes.iter().enumerate() {
+            let tick = self.last_used[old].load(Ordering::Relaxed);
+            capped.last_used.push(AtomicU64::new(tick));
+            if old < self.oldest {
+                capped.oldest = index + 1;
+            }
+        }
+        if capped.oldest == indexes.len() {
+            capped.oldest = 0;
+        }
+        if self.eviction == Eviction::LeastRecentlyUsed {
+            capped.recency = capped
+                .last_used
+                .iter()
+                .enumerate()
+                .map(|(index, tick)| Reverse((tick.load(Ordering::Relaxed), index)))
+                .collect();
+        }
+        capped
+    }
+
+    pub(crate) fn inserted(&mut self) {
+        let tick = self.tick();
+        if self.eviction == Eviction::LeastRecentlyUsed {
+            self.recency.push(Reverse((tick, self.last_used.len())));
+        }
+        self.last_used.push(AtomicU64::new(tick));
+        self.replaced.push(0);
+    }
+
+    pub(crate) fn replaced<T>(&mut self, row: Row<T>, generation: u64) {
+        if let Some(replaced) = self.replaced.get_mut(row.as_index()) {
+            *replaced = generation;
+        }
+    }
+
+    pub(crate) fn replaced_since<T>(&self, row: Row<T>, generation: u64) -> bool {
+        self.replaced
+            .get(row.as_index())
+            .is_some_and(|&replaced| replaced > generation)
+    }
+
+    pub(crate) fn touch<T>(&self, row: Row<T>) {
+        if let Some(last_used) = self.last_used.get(row.as_index()) {
+            last_used.store(self.tick(), Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn victim<T, I: Indexer<T>>(&mut self, indexer: &I) -> Option<Row<T>> {
+        if self.last_used.is_empty() || self.last_used.len() < self.max_rows {
+            return None;
+        }
+        let index = match self.eviction {
+            Eviction::Oldest => self.next_oldest(),
+            Eviction::LeastRecentlyUsed => self.least_recently_used()?,
+            // an indexer without a victim hook would otherwise let the table grow past the cap
+            Eviction::LowestPriority => match indexer.victim() {
+                Some(row) => return Some(row),
+                None => self.next_oldest(),
+            },
+        };
+        Some(unsafe { Row::from_index(index) })
+    }
+
+    fn next_oldest(&mut self) -> usize {
+        let index = self.oldest;
+        self.oldest = (index + 1) % self.last_used.len();
+        index
+    }
+
+    fn least_recently_used(&mut self) -> Option<usize> {
+        while let Some(Reverse((tick, index))) = self.recency.pop() {
+            let last_used = self.last_used[index].load(Ordering::Relaxed);
+            if last_used == tick {
+                let tick = self.tick();
+                self.last_used[index].store(tick, Ordering::Relaxed);
+                self.recency.push(Reverse((tick, index)));
+                return Some(index);
+            }
+            self.recency.push(Reverse((last_used, index)));
+        }
+        None
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+}
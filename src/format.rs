@@ -0,0 +1,53 @@
+pub trait Columns {
+    fn headers() -> Vec<&'static str>;
+    fn columns(&self) -> Vec<String>;
+}
+
+impl<T> Columns for &T
+where
+    T: Columns,
+{
+    fn headers() -> Vec<&'static str> {
+        T::headers()
+    }
+
+    fn columns(&self) -> Vec<String> {
+        (*self).columns()
+    }
+}
+
+pub fn format_table<T, I>(items: I) -> String
+where
+    T: Columns,
+    I: IntoIterator<Item = T>,
+{
+    let headers: Vec<String> = T::headers().into_iter().map(String::from).collect();
+    let rows: Vec<Vec<String>> = items.into_iter().map(|item| item.columns()).collect();
+
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut output = format_line(&headers, &widths);
+    let separator: Vec<String> = widths.iter().map(|&width| "-".repeat(width)).collect();
+    output.push_str(&separator.join("-+-"));
+    output.push('\n');
+    for row in &rows {
+        output.push_str(&format_line(row, &widths));
+    }
+    output
+}
+
+fn format_line(cells: &[String], widths: &[usize]) -> String {
+    let padded: Vec<String> = cells
+        .iter()
+        .zip(widths)
+        .map(|(cell, &width)| format!("{:width$}", cell, width = width))
+        .collect();
+    let mut line = padded.join(" | ").trim_end().to_string();
+    line.push('\n');
+    line
+}
@@ -1,22 +1,114 @@
-use std::borrow::Borrow;
-use std::collections::HashMap;
-use std::hash::Hash;
+use std::borrow::{Borrow, Cow};
+use std::cmp::Ordering;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Bound::{Excluded, Included, Unbounded};
+use std::ops::{AddAssign, RangeBounds, SubAssign};
+use std::time::{Duration, SystemTime};
 
 use crate::selection::{Row, Selection};
 
 pub trait Index<T> {
     fn add(&mut self, row: Row<T>, item: &T);
     fn remove(&mut self, row: Row<T>, item: &T);
+
+    fn stats(&self) -> IndexStats {
+        IndexStats::default()
+    }
+
+    fn add_many(&mut self, items: &[(Row<T>, &T)]) {
+        for &(row, item) in items {
+            self.add(row, item);
+        }
+    }
+
+    fn remove_many(&mut self, items: &[(Row<T>, &T)]) {
+        for &(row, item) in items {
+            self.remove(row, item);
+        }
+    }
+
+    fn before_update(&mut self, row: Row<T>, item: &T) {
+        self.remove(row, item);
+    }
+
+    fn after_update(&mut self, row: Row<T>, item: &T) {
+        self.add(row, item);
+    }
+
+    fn before_update_many(&mut self, items: &[(Row<T>, &T)]) {
+        self.remove_many(items);
+    }
+
+    fn after_update_many(&mut self, items: &[(Row<T>, &T)]) {
+        self.add_many(items);
+    }
+
+    fn warm(&mut self) {}
+}
+
+pub trait Lookup<T, K: ?Sized> {
+    fn lookup(&self, key: &K) -> Selection<T>;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IndexStats {
+    pub keys: usize,
+    pub rows: u64,
+}
+
+// keys taken in before_update, so after_update only moves rows whose key changed
+
+struct Pending<T, K> {
+    keys: HashMap<Row<T>, K>,
+}
+
+impl<T, K> Default for Pending<T, K> {
+    fn default() -> Self {
+        Self {
+            keys: HashMap::new(),
+        }
+    }
+}
+
+impl<T, K> Pending<T, K> {
+    fn stash(&mut self, row: Row<T>, key: K) {
+        self.keys.insert(row, key);
+    }
+
+    fn take(&mut self, row: Row<T>) -> Option<K> {
+        self.keys.remove(&row)
+    }
+}
+
+// batched updates skip the stash: rows no longer held under their new key are the
+// ones that moved, and only those get detached from every old key and re-added
+
+fn moved<'item, T>(
+    items: &[(Row<T>, &'item T)],
+    held: impl Fn(Row<T>, &T) -> bool,
+) -> (Selection<T>, Vec<(Row<T>, &'item T)>) {
+    let moved: Vec<_> = items
+        .iter()
+        .copied()
+        .filter(|&(row, item)| !held(row, item))
+        .collect();
+    (moved.iter().map(|&(row, _)| row).collect(), moved)
 }
 
 // unique
 
-pub struct UniqueIndex<T, V>
+pub struct UniqueIndex<T, V, S = RandomState>
 where
     V: Eq + Hash,
 {
     predicate: fn(&T) -> V,
-    rows: HashMap<V, Row<T>>,
+    rows: HashMap<V, Row<T>, S>,
+    pending: Pending<T, V>,
 }
 
 impl<T, V> UniqueIndex<T, V>
@@ -24,9 +116,20 @@ where
     V: Eq + Hash,
 {
     pub fn new(predicate: fn(&T) -> V) -> Self {
+        Self::with_hasher(predicate, RandomState::new())
+    }
+}
+
+impl<T, V, S> UniqueIndex<T, V, S>
+where
+    V: Eq + Hash,
+    S: BuildHasher,
+{
+    pub fn with_hasher(predicate: fn(&T) -> V, hasher: S) -> Self {
         Self {
             predicate,
-            rows: HashMap::new(),
+            rows: HashMap::with_hasher(hasher),
+            pending: Pending::default(),
         }
     }
 
@@ -37,11 +140,36 @@ where
     {
         self.rows.get(value).copied()
     }
+
+    pub fn conflict(&self, item: &T) -> Option<Row<T>> {
+        self.rows.get(&(self.predicate)(item)).copied()
+    }
+
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        V: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.rows.contains_key(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &V> + '_ {
+        self.rows.keys()
+    }
 }
 
-impl<T, V> Index<T> for UniqueIndex<T, V>
+impl<T, V, S> Index<T> for UniqueIndex<T, V, S>
 where
     V: Eq + Hash,
+    S: BuildHasher,
 {
     fn add(&mut self, row: Row<T>, item: &T) {
         self.rows.insert((self.predicate)(item), row);
@@ -50,17 +178,311 @@ where
     fn remove(&mut self, _row: Row<T>, item: &T) {
         self.rows.remove(&(self.predicate)(item));
     }
+
+    fn stats(&self) -> IndexStats {
+        IndexStats {
+            keys: self.rows.len(),
+            rows: self.rows.len() as u64,
+        }
+    }
+
+    fn before_update(&mut self, row: Row<T>, item: &T) {
+        self.pending.stash(row, (self.predicate)(item));
+    }
+
+    fn after_update(&mut self, row: Row<T>, item: &T) {
+        let key = (self.predicate)(item);
+        match self.pending.take(row) {
+            Some(old) if old == key => {}
+            Some(old) => {
+                self.rows.remove(&old);
+                self.rows.insert(key, row);
+            }
+            None => {
+                self.rows.insert(key, row);
+            }
+        }
+    }
+
+    fn before_update_many(&mut self, _items: &[(Row<T>, &T)]) {}
+
+    fn after_update_many(&mut self, items: &[(Row<T>, &T)]) {
+        let (rows, moved) = moved(items, |row, item| {
+            self.rows.get(&(self.predicate)(item)) == Some(&row)
+        });
+        if moved.is_empty() {
+            return;
+        }
+        self.rows.retain(|_, row| !rows.contains(*row));
+        self.add_many(&moved);
+    }
+}
+
+impl<T, V, S, Q> Lookup<T, Q> for UniqueIndex<T, V, S>
+where
+    V: Eq + Hash + Borrow<Q>,
+    S: BuildHasher,
+    Q: Eq + Hash + ?Sized,
+{
+    fn lookup(&self, key: &Q) -> Selection<T> {
+        self.get(key).map(Selection::of_row).unwrap_or_default()
+    }
+}
+
+// dense
+
+pub struct DenseIndex<T, K = u64> {
+    predicate: fn(&T) -> K,
+    rows: DenseSlots<Row<T>>,
+    pending: Pending<T, u64>,
+}
+
+impl<T, K> DenseIndex<T, K>
+where
+    K: Copy + Into<u64>,
+{
+    pub fn new(predicate: fn(&T) -> K) -> Self {
+        Self {
+            predicate,
+            rows: DenseSlots::new(),
+            pending: Pending::default(),
+        }
+    }
+
+    pub fn get(&self, key: K) -> Option<Row<T>> {
+        self.rows.get(key.into()).copied()
+    }
+
+    pub fn conflict(&self, item: &T) -> Option<Row<T>> {
+        self.get((self.predicate)(item))
+    }
+}
+
+impl<T, K> Index<T> for DenseIndex<T, K>
+where
+    K: Copy + Into<u64>,
+{
+    fn add(&mut self, row: Row<T>, item: &T) {
+        self.rows.insert((self.predicate)(item).into(), row);
+    }
+
+    fn remove(&mut self, _row: Row<T>, item: &T) {
+        self.rows.remove((self.predicate)(item).into());
+    }
+
+    fn stats(&self) -> IndexStats {
+        IndexStats {
+            keys: self.rows.len(),
+            rows: self.rows.len() as u64,
+        }
+    }
+
+    fn before_update(&mut self, row: Row<T>, item: &T) {
+        self.pending.stash(row, (self.predicate)(item).into());
+    }
+
+    fn after_update(&mut self, row: Row<T>, item: &T) {
+        let key = (self.predicate)(item).into();
+        match self.pending.take(row) {
+            Some(old) if old == key => {}
+            Some(old) => {
+                self.rows.remove(old);
+                self.rows.insert(key, row);
+            }
+            None => {
+                self.rows.insert(key, row);
+            }
+        }
+    }
+}
+
+impl<T, K> Lookup<T, K> for DenseIndex<T, K>
+where
+    K: Copy + Into<u64>,
+{
+    fn lookup(&self, key: &K) -> Selection<T> {
+        self.get(*key).map(Selection::of_row).unwrap_or_default()
+    }
+}
+
+pub struct DenseDiscreteIndex<T, K = u64> {
+    predicate: fn(&T) -> K,
+    selections: DenseSlots<Selection<T>>,
+    empty: Selection<T>,
+    pending: Pending<T, u64>,
+}
+
+impl<T, K> DenseDiscreteIndex<T, K>
+where
+    K: Copy + Into<u64>,
+{
+    pub fn new(predicate: fn(&T) -> K) -> Self {
+        Self {
+            predicate,
+            selections: DenseSlots::new(),
+            empty: Selection::empty(),
+            pending: Pending::default(),
+        }
+    }
+
+    pub fn get(&self, key: K) -> &Selection<T> {
+        self.selections.get(key.into()).unwrap_or(&self.empty)
+    }
+
+    fn remove_key(&mut self, row: Row<T>, key: u64) {
+        if let Some(selection) = self.selections.get_mut(key) {
+            selection.remove(row);
+            if selection.is_empty() {
+                self.selections.remove(key);
+            }
+        }
+    }
+}
+
+impl<T, K> Index<T> for DenseDiscreteIndex<T, K>
+where
+    K: Copy + Into<u64>,
+{
+    fn add(&mut self, row: Row<T>, item: &T) {
+        let key = (self.predicate)(item).into();
+        match self.selections.get_mut(key) {
+            Some(selection) => selection.add(row),
+            None => {
+                self.selections.insert(key, Selection::of_row(row));
+            }
+        }
+    }
+
+    fn remove(&mut self, row: Row<T>, item: &T) {
+        self.remove_key(row, (self.predicate)(item).into());
+    }
+
+    fn stats(&self) -> IndexStats {
+        IndexStats {
+            keys: self.selections.len(),
+            rows: self.selections.values().map(Selection::len).sum(),
+        }
+    }
+
+    fn before_update(&mut self, row: Row<T>, item: &T) {
+        self.pending.stash(row, (self.predicate)(item).into());
+    }
+
+    fn after_update(&mut self, row: Row<T>, item: &T) {
+        match self.pending.take(row) {
+            Some(old) if old == (self.predicate)(item).into() => {}
+            Some(old) => {
+                self.remove_key(row, old);
+                self.add(row, item);
+            }
+            None => self.add(row, item),
+        }
+    }
+}
+
+impl<T, K> Lookup<T, K> for DenseDiscreteIndex<T, K>
+where
+    K: Copy + Into<u64>,
+{
+    fn lookup(&self, key: &K) -> Selection<T> {
+        self.get(*key).clone()
+    }
+}
+
+// keys are kept in the vec only while it stays at least half full,
+// anything sparser goes to the map
+const DENSE_SLACK: usize = 1024;
+
+struct DenseSlots<V> {
+    dense: Vec<Option<V>>,
+    sparse: HashMap<u64, V>,
+    len: usize,
+}
+
+impl<V> DenseSlots<V> {
+    fn new() -> Self {
+        Self {
+            dense: Vec::new(),
+            sparse: HashMap::new(),
+            len: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn get(&self, key: u64) -> Option<&V> {
+        match self.slot(key) {
+            Some(Some(value)) => Some(value),
+            _ => self.sparse.get(&key),
+        }
+    }
+
+    fn get_mut(&mut self, key: u64) -> Option<&mut V> {
+        if let Some(index) = self.dense_index(key) {
+            if let Some(Some(value)) = self.dense.get_mut(index) {
+                return Some(value);
+            }
+        }
+        self.sparse.get_mut(&key)
+    }
+
+    fn insert(&mut self, key: u64, value: V) -> Option<V> {
+        if let Some(old) = self.sparse.get_mut(&key) {
+            return Some(mem::replace(old, value));
+        }
+        let previous = match self.dense_index(key) {
+            Some(index) if index < self.dense.len() => self.dense[index].replace(value),
+            Some(index) if index < 2 * self.len + DENSE_SLACK => {
+                self.dense.resize_with(index + 1, || None);
+                self.dense[index].replace(value)
+            }
+            _ => self.sparse.insert(key, value),
+        };
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous
+    }
+
+    fn remove(&mut self, key: u64) -> Option<V> {
+        let removed = match self
+            .dense_index(key)
+            .and_then(|index| self.dense.get_mut(index))
+        {
+            Some(slot) if slot.is_some() => slot.take(),
+            _ => self.sparse.remove(&key),
+        };
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    fn values(&self) -> impl Iterator<Item = &V> + '_ {
+        self.dense.iter().flatten().chain(self.sparse.values())
+    }
+
+    fn slot(&self, key: u64) -> Option<&Option<V>> {
+        self.dense.get(self.dense_index(key)?)
+    }
+
+    fn dense_index(&self, key: u64) -> Option<usize> {
+        usize::try_from(key).ok()
+    }
 }
 
 // discrete
 
-pub struct DiscreteIndex<T, V>
+pub struct DiscreteIndex<T, V, S = RandomState>
 where
     V: Eq + Hash,
 {
     predicate: fn(&T) -> &V,
-    selections: HashMap<V, Selection<T>>,
+    selections: HashMap<V, Selection<T>, S>,
     empty: Selection<T>,
+    pending: Pending<T, V>,
 }
 
 impl<T, V> DiscreteIndex<T, V>
@@ -68,10 +490,21 @@ where
     V: Eq + Hash,
 {
     pub fn new(predicate: fn(&T) -> &V) -> Self {
+        Self::with_hasher(predicate, RandomState::new())
+    }
+}
+
+impl<T, V, S> DiscreteIndex<T, V, S>
+where
+    V: Eq + Hash,
+    S: BuildHasher,
+{
+    pub fn with_hasher(predicate: fn(&T) -> &V, hasher: S) -> Self {
         Self {
             predicate,
-            selections: HashMap::new(),
+            selections: HashMap::with_hasher(hasher),
             empty: Selection::empty(),
+            pending: Pending::default(),
         }
     }
 
@@ -82,11 +515,55 @@ where
     {
         self.selections.get(value).unwrap_or(&self.empty)
     }
+
+    pub fn len(&self) -> usize {
+        self.selections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.selections.is_empty()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &V> + '_ {
+        self.selections.keys()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&V, &Selection<T>)> + '_ {
+        self.selections.iter()
+    }
+
+    pub(crate) fn key<'item>(&self, item: &'item T) -> &'item V {
+        (self.predicate)(item)
+    }
+
+    pub fn facet(&self, within: &Selection<T>) -> HashMap<&V, u64> {
+        self.selections
+            .iter()
+            .map(|(key, selection)| (key, selection.intersection_len(within)))
+            .filter(|&(_, count)| count > 0)
+            .collect()
+    }
+
+    pub fn duplicates(&self) -> impl Iterator<Item = (&V, &Selection<T>)> + '_ {
+        self.selections
+            .iter()
+            .filter(|(_, selection)| selection.len() > 1)
+    }
+
+    fn remove_key(&mut self, row: Row<T>, key: &V) {
+        if let Some(selection) = self.selections.get_mut(key) {
+            selection.remove(row);
+            if selection.is_empty() {
+                self.selections.remove(key);
+            }
+        }
+    }
 }
 
-impl<T, V> Index<T> for DiscreteIndex<T, V>
+impl<T, V, S> Index<T> for DiscreteIndex<T, V, S>
 where
     V: Eq + Hash + Clone,
+    S: BuildHasher,
 {
     fn add(&mut self, row: Row<T>, item: &T) {
         let key = (self.predicate)(item);
@@ -101,41 +578,2053 @@ where
     }
 
     fn remove(&mut self, row: Row<T>, item: &T) {
-        let key = (self.predicate)(item);
-        if let Some(selection) = self.selections.get_mut(key) {
-            selection.remove(row);
-        }
+        self.remove_key(row, (self.predicate)(item));
     }
-}
 
-// boolean
+    fn stats(&self) -> IndexStats {
+        IndexStats {
+            keys: self.selections.len(),
+            rows: self.selections.values().map(Selection::len).sum(),
+        }
+    }
 
-pub struct BooleanIndex<T> {
-    predicate: fn(&T) -> bool,
-    selection: Selection<T>,
-}
+    fn before_update(&mut self, row: Row<T>, item: &T) {
+        self.pending.stash(row, (self.predicate)(item).clone());
+    }
 
-impl<T> BooleanIndex<T> {
-    pub fn new(predicate: fn(&T) -> bool) -> Self {
-        Self {
-            predicate,
-            selection: Selection::empty(),
+    fn after_update(&mut self, row: Row<T>, item: &T) {
+        match self.pending.take(row) {
+            Some(old) if old == *(self.predicate)(item) => {}
+            Some(old) => {
+                self.remove_key(row, &old);
+                self.add(row, item);
+            }
+            None => self.add(row, item),
         }
     }
 
-    pub fn get(&self) -> &Selection<T> {
-        &self.selection
-    }
-}
+    fn before_update_many(&mut self, _items: &[(Row<T>, &T)]) {}
 
-impl<T> Index<T> for BooleanIndex<T> {
-    fn add(&mut self, row: Row<T>, item: &T) {
-        if (self.predicate)(item) {
-            self.selection.add(row);
+    fn after_update_many(&mut self, items: &[(Row<T>, &T)]) {
+        let (rows, moved) = moved(items, |row, item| {
+            self.get((self.predicate)(item)).contains(row)
+        });
+        if moved.is_empty() {
+            return;
         }
+        self.selections.retain(|_, selection| {
+            *selection -= &rows;
+            !selection.is_empty()
+        });
+        self.add_many(&moved);
     }
 
-    fn remove(&mut self, row: Row<T>, _item: &T) {
+    fn add_many(&mut self, items: &[(Row<T>, &T)]) {
+        let mut batches: HashMap<&V, Selection<T>> = HashMap::new();
+        for &(row, item) in items {
+            batches.entry((self.predicate)(item)).or_default().add(row);
+        }
+        for (key, batch) in batches {
+            match self.selections.get_mut(key) {
+                Some(selection) => *selection |= &batch,
+                None => {
+                    self.selections.insert(key.clone(), batch);
+                }
+            }
+        }
+    }
+
+    fn remove_many(&mut self, items: &[(Row<T>, &T)]) {
+        let mut batches: HashMap<&V, Selection<T>> = HashMap::new();
+        for &(row, item) in items {
+            batches.entry((self.predicate)(item)).or_default().add(row);
+        }
+        for (key, batch) in batches {
+            if let Some(selection) = self.selections.get_mut(key) {
+                *selection -= &batch;
+                if selection.is_empty() {
+                    self.selections.remove(key);
+                }
+            }
+        }
+    }
+
+    fn warm(&mut self) {
+        for selection in self.selections.values_mut() {
+            selection.optimize();
+        }
+    }
+}
+
+pub fn join_selections<'index, A, B, V, S, I>(
+    left: &'index DiscreteIndex<A, V, S>,
+    right: &'index I,
+) -> impl Iterator<Item = (Row<A>, Row<B>)> + 'index
+where
+    V: Eq + Hash,
+    S: BuildHasher,
+    B: 'index,
+    I: Lookup<B, V>,
+{
+    left.iter().flat_map(move |(key, rows)| {
+        let matches = right.lookup(key);
+        let pairs: Vec<_> = rows
+            .rows()
+            .flat_map(|row| matches.rows().map(move |other| (row, other)))
+            .collect();
+        pairs
+    })
+}
+
+impl<T, V, S, Q> Lookup<T, Q> for DiscreteIndex<T, V, S>
+where
+    V: Eq + Hash + Borrow<Q>,
+    S: BuildHasher,
+    Q: Eq + Hash + ?Sized,
+{
+    fn lookup(&self, key: &Q) -> Selection<T> {
+        self.get(key).clone()
+    }
+}
+
+// expression
+
+pub struct ExpressionIndex<T, V, S = RandomState>
+where
+    V: Eq + Hash,
+{
+    expression: fn(&T) -> V,
+    selections: HashMap<V, Selection<T>, S>,
+    empty: Selection<T>,
+    pending: Pending<T, V>,
+}
+
+impl<T, V> ExpressionIndex<T, V>
+where
+    V: Eq + Hash,
+{
+    pub fn new(expression: fn(&T) -> V) -> Self {
+        Self::with_hasher(expression, RandomState::new())
+    }
+}
+
+impl<T, V, S> ExpressionIndex<T, V, S>
+where
+    V: Eq + Hash,
+    S: BuildHasher,
+{
+    pub fn with_hasher(expression: fn(&T) -> V, hasher: S) -> Self {
+        Self {
+            expression,
+            selections: HashMap::with_hasher(hasher),
+            empty: Selection::empty(),
+            pending: Pending::default(),
+        }
+    }
+
+    pub fn get<Q>(&self, value: &Q) -> &Selection<T>
+    where
+        V: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.selections.get(value).unwrap_or(&self.empty)
+    }
+
+    fn remove_key(&mut self, row: Row<T>, key: &V) {
+        if let Some(selection) = self.selections.get_mut(key) {
+            selection.remove(row);
+            if selection.is_empty() {
+                self.selections.remove(key);
+            }
+        }
+    }
+}
+
+impl<T, V, S> Index<T> for ExpressionIndex<T, V, S>
+where
+    V: Eq + Hash,
+    S: BuildHasher,
+{
+    fn add(&mut self, row: Row<T>, item: &T) {
+        self.selections
+            .entry((self.expression)(item))
+            .or_default()
+            .add(row);
+    }
+
+    fn remove(&mut self, row: Row<T>, item: &T) {
+        self.remove_key(row, &(self.expression)(item));
+    }
+
+    fn stats(&self) -> IndexStats {
+        IndexStats {
+            keys: self.selections.len(),
+            rows: self.selections.values().map(Selection::len).sum(),
+        }
+    }
+
+    fn before_update(&mut self, row: Row<T>, item: &T) {
+        self.pending.stash(row, (self.expression)(item));
+    }
+
+    fn after_update(&mut self, row: Row<T>, item: &T) {
+        match self.pending.take(row) {
+            Some(old) if old == (self.expression)(item) => {}
+            Some(old) => {
+                self.remove_key(row, &old);
+                self.add(row, item);
+            }
+            None => self.add(row, item),
+        }
+    }
+
+    fn before_update_many(&mut self, _items: &[(Row<T>, &T)]) {}
+
+    fn after_update_many(&mut self, items: &[(Row<T>, &T)]) {
+        let (rows, moved) = moved(items, |row, item| {
+            self.get(&(self.expression)(item)).contains(row)
+        });
+        if moved.is_empty() {
+            return;
+        }
+        self.selections.retain(|_, selection| {
+            *selection -= &rows;
+            !selection.is_empty()
+        });
+        self.add_many(&moved);
+    }
+
+    fn warm(&mut self) {
+        for selection in self.selections.values_mut() {
+            selection.optimize();
+        }
+    }
+}
+
+impl<T, V, S, Q> Lookup<T, Q> for ExpressionIndex<T, V, S>
+where
+    V: Eq + Hash + Borrow<Q>,
+    S: BuildHasher,
+    Q: Eq + Hash + ?Sized,
+{
+    fn lookup(&self, key: &Q) -> Selection<T> {
+        self.get(key).clone()
+    }
+}
+
+// adaptive
+
+pub struct AdaptiveIndex<T, V>
+where
+    V: Eq + Hash,
+{
+    predicate: fn(&T) -> &V,
+    threshold: usize,
+    keys: HashMap<V, Adaptive<T>>,
+    empty: Selection<T>,
+    pending: Pending<T, V>,
+}
+
+enum Adaptive<T> {
+    Small(Vec<Row<T>>),
+    Large(Selection<T>),
+}
+
+impl<T, V> AdaptiveIndex<T, V>
+where
+    V: Eq + Hash,
+{
+    pub fn new(predicate: fn(&T) -> &V, threshold: usize) -> Self {
+        Self {
+            predicate,
+            threshold: threshold.max(1),
+            keys: HashMap::new(),
+            empty: Selection::empty(),
+            pending: Pending::default(),
+        }
+    }
+
+    pub fn get<Q>(&self, value: &Q) -> Cow<'_, Selection<T>>
+    where
+        V: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        match self.keys.get(value) {
+            Some(Adaptive::Small(rows)) => Cow::Owned(rows.iter().copied().collect()),
+            Some(Adaptive::Large(selection)) => Cow::Borrowed(selection),
+            None => Cow::Borrowed(&self.empty),
+        }
+    }
+
+    pub fn is_bitmap(&self, value: &V) -> bool {
+        matches!(self.keys.get(value), Some(Adaptive::Large(_)))
+    }
+
+    fn remove_key(&mut self, row: Row<T>, key: &V) {
+        let rows = match self.keys.get_mut(key) {
+            Some(rows) => rows,
+            None => return,
+        };
+        let empty = match rows {
+            Adaptive::Small(small) => {
+                if let Ok(position) = small.binary_search(&row) {
+                    small.remove(position);
+                }
+                small.is_empty()
+            }
+            Adaptive::Large(selection) => {
+                selection.remove(row);
+                let empty = selection.is_empty();
+                if !empty && selection.len() as usize <= self.threshold / 2 {
+                    *rows = Adaptive::Small(selection.rows().collect());
+                }
+                empty
+            }
+        };
+        if empty {
+            self.keys.remove(key);
+        }
+    }
+}
+
+impl<T, V> Index<T> for AdaptiveIndex<T, V>
+where
+    V: Eq + Hash + Clone,
+{
+    fn add(&mut self, row: Row<T>, item: &T) {
+        let key = (self.predicate)(item);
+        let rows = match self.keys.get_mut(key) {
+            Some(rows) => rows,
+            None => self
+                .keys
+                .entry(key.clone())
+                .or_insert_with(|| Adaptive::Small(Vec::new())),
+        };
+        match rows {
+            Adaptive::Small(small) => {
+                if let Err(position) = small.binary_search(&row) {
+                    small.insert(position, row);
+                }
+                if small.len() > self.threshold {
+                    *rows = Adaptive::Large(small.iter().copied().collect());
+                }
+            }
+            Adaptive::Large(selection) => selection.add(row),
+        }
+    }
+
+    fn remove(&mut self, row: Row<T>, item: &T) {
+        self.remove_key(row, (self.predicate)(item));
+    }
+
+    fn stats(&self) -> IndexStats {
+        IndexStats {
+            keys: self.keys.len(),
+            rows: self
+                .keys
+                .values()
+                .map(|rows| match rows {
+                    Adaptive::Small(small) => small.len() as u64,
+                    Adaptive::Large(selection) => selection.len(),
+                })
+                .sum(),
+        }
+    }
+
+    fn before_update(&mut self, row: Row<T>, item: &T) {
+        self.pending.stash(row, (self.predicate)(item).clone());
+    }
+
+    fn after_update(&mut self, row: Row<T>, item: &T) {
+        match self.pending.take(row) {
+            Some(old) if old == *(self.predicate)(item) => {}
+            Some(old) => {
+                self.remove_key(row, &old);
+                self.add(row, item);
+            }
+            None => self.add(row, item),
+        }
+    }
+}
+
+impl<T, V, Q> Lookup<T, Q> for AdaptiveIndex<T, V>
+where
+    V: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ?Sized,
+{
+    fn lookup(&self, key: &Q) -> Selection<T> {
+        self.get(key).into_owned()
+    }
+}
+
+// sorted
+
+pub struct SortedIndex<T, V>
+where
+    V: Ord,
+{
+    predicate: fn(&T) -> &V,
+    selections: BTreeMap<V, Selection<T>>,
+    empty: Selection<T>,
+    pending: Pending<T, V>,
+}
+
+impl<T, V> SortedIndex<T, V>
+where
+    V: Ord,
+{
+    pub fn new(predicate: fn(&T) -> &V) -> Self {
+        Self {
+            predicate,
+            selections: BTreeMap::new(),
+            empty: Selection::empty(),
+            pending: Pending::default(),
+        }
+    }
+
+    pub fn get<Q>(&self, value: &Q) -> &Selection<T>
+    where
+        V: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.selections.get(value).unwrap_or(&self.empty)
+    }
+
+    pub fn range<Q, R>(&self, range: R) -> Selection<T>
+    where
+        V: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let mut result = Selection::empty();
+        for selection in self.selections.range(range).map(|(_, selection)| selection) {
+            result |= selection;
+        }
+        result
+    }
+
+    pub(crate) fn key<'item>(&self, item: &'item T) -> &'item V {
+        (self.predicate)(item)
+    }
+
+    pub(crate) fn key_count(&self) -> usize {
+        self.selections.len()
+    }
+
+    fn remove_key(&mut self, row: Row<T>, key: &V) {
+        if let Some(selection) = self.selections.get_mut(key) {
+            selection.remove(row);
+            if selection.is_empty() {
+                self.selections.remove(key);
+            }
+        }
+    }
+
+    pub fn page_after(&self, after: Option<(&V, Row<T>)>, limit: usize) -> Vec<Row<T>> {
+        self.page(None, after, limit)
+    }
+
+    pub fn page_within(
+        &self,
+        selection: &Selection<T>,
+        after: Option<(&V, Row<T>)>,
+        limit: usize,
+    ) -> Vec<Row<T>> {
+        self.page(Some(selection), after, limit)
+    }
+
+    fn page(
+        &self,
+        within: Option<&Selection<T>>,
+        after: Option<(&V, Row<T>)>,
+        limit: usize,
+    ) -> Vec<Row<T>> {
+        let mut rows = Vec::new();
+        if limit == 0 {
+            return rows;
+        }
+
+        let start = match after {
+            Some((key, _)) => Included(key),
+            None => Unbounded,
+        };
+        for (key, selection) in self.selections.range((start, Unbounded)) {
+            let matching;
+            let selection = match within {
+                Some(within) => {
+                    matching = selection & within;
+                    &matching
+                }
+                None => selection,
+            };
+            for row in selection.rows() {
+                if let Some((after_key, after_row)) = after {
+                    if key == after_key && row.as_index() <= after_row.as_index() {
+                        continue;
+                    }
+                }
+                rows.push(row);
+                if rows.len() == limit {
+                    return rows;
+                }
+            }
+        }
+        rows
+    }
+}
+
+impl<T, V> Index<T> for SortedIndex<T, V>
+where
+    V: Ord + Clone,
+{
+    fn add(&mut self, row: Row<T>, item: &T) {
+        let key = (self.predicate)(item);
+        match self.selections.get_mut(key) {
+            Some(selection) => selection.add(row),
+            None => {
+                self.selections.insert(key.clone(), Selection::of_row(row));
+            }
+        };
+    }
+
+    fn remove(&mut self, row: Row<T>, item: &T) {
+        self.remove_key(row, (self.predicate)(item));
+    }
+
+    fn stats(&self) -> IndexStats {
+        IndexStats {
+            keys: self.selections.len(),
+            rows: self.selections.values().map(Selection::len).sum(),
+        }
+    }
+
+    fn before_update(&mut self, row: Row<T>, item: &T) {
+        self.pending.stash(row, (self.predicate)(item).clone());
+    }
+
+    fn after_update(&mut self, row: Row<T>, item: &T) {
+        match self.pending.take(row) {
+            Some(old) if old == *(self.predicate)(item) => {}
+            Some(old) => {
+                self.remove_key(row, &old);
+                self.add(row, item);
+            }
+            None => self.add(row, item),
+        }
+    }
+
+    fn before_update_many(&mut self, _items: &[(Row<T>, &T)]) {}
+
+    fn after_update_many(&mut self, items: &[(Row<T>, &T)]) {
+        let (rows, moved) = moved(items, |row, item| {
+            self.get((self.predicate)(item)).contains(row)
+        });
+        if moved.is_empty() {
+            return;
+        }
+        self.selections.retain(|_, selection| {
+            *selection -= &rows;
+            !selection.is_empty()
+        });
+        self.add_many(&moved);
+    }
+
+    fn add_many(&mut self, items: &[(Row<T>, &T)]) {
+        let mut batches: BTreeMap<&V, Selection<T>> = BTreeMap::new();
+        for &(row, item) in items {
+            batches.entry((self.predicate)(item)).or_default().add(row);
+        }
+        for (key, batch) in batches {
+            match self.selections.get_mut(key) {
+                Some(selection) => *selection |= &batch,
+                None => {
+                    self.selections.insert(key.clone(), batch);
+                }
+            }
+        }
+    }
+
+    fn remove_many(&mut self, items: &[(Row<T>, &T)]) {
+        let mut batches: BTreeMap<&V, Selection<T>> = BTreeMap::new();
+        for &(row, item) in items {
+            batches.entry((self.predicate)(item)).or_default().add(row);
+        }
+        for (key, batch) in batches {
+            if let Some(selection) = self.selections.get_mut(key) {
+                *selection -= &batch;
+                if selection.is_empty() {
+                    self.selections.remove(key);
+                }
+            }
+        }
+    }
+
+    fn warm(&mut self) {
+        for selection in self.selections.values_mut() {
+            selection.optimize();
+        }
+    }
+}
+
+impl<T, V, Q> Lookup<T, Q> for SortedIndex<T, V>
+where
+    V: Ord + Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    fn lookup(&self, key: &Q) -> Selection<T> {
+        self.get(key).clone()
+    }
+}
+
+// comparator
+
+pub struct ComparatorIndex<T, V> {
+    predicate: fn(&T) -> V,
+    compare: fn(&V, &V) -> Ordering,
+    keys: BTreeMap<Compared<V>, Selection<T>>,
+    empty: Selection<T>,
+    pending: Pending<T, Compared<V>>,
+}
+
+impl<T, V> ComparatorIndex<T, V> {
+    pub fn new(predicate: fn(&T) -> V, compare: fn(&V, &V) -> Ordering) -> Self {
+        Self {
+            predicate,
+            compare,
+            keys: BTreeMap::new(),
+            empty: Selection::empty(),
+            pending: Pending::default(),
+        }
+    }
+
+    pub fn get(&self, value: &V) -> &Selection<T>
+    where
+        V: Clone,
+    {
+        self.keys
+            .get(&self.compared(value.clone()))
+            .unwrap_or(&self.empty)
+    }
+
+    pub fn between(&self, from: &V, to: &V) -> Selection<T>
+    where
+        V: Clone,
+    {
+        let (from, to) = (self.compared(from.clone()), self.compared(to.clone()));
+        let mut result = Selection::empty();
+        if from <= to {
+            for (_, selection) in self.keys.range(from..=to) {
+                result |= selection;
+            }
+        }
+        result
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = Row<T>> + '_ {
+        self.keys.values().flat_map(Selection::rows)
+    }
+
+    fn compared(&self, value: V) -> Compared<V> {
+        Compared {
+            value,
+            compare: self.compare,
+        }
+    }
+
+    fn remove_key(&mut self, row: Row<T>, key: &Compared<V>) {
+        if let Some(selection) = self.keys.get_mut(key) {
+            selection.remove(row);
+            if selection.is_empty() {
+                self.keys.remove(key);
+            }
+        }
+    }
+}
+
+impl<T, V> Index<T> for ComparatorIndex<T, V> {
+    fn add(&mut self, row: Row<T>, item: &T) {
+        let key = self.compared((self.predicate)(item));
+        match self.keys.get_mut(&key) {
+            Some(selection) => selection.add(row),
+            None => {
+                self.keys.insert(key, Selection::of_row(row));
+            }
+        }
+    }
+
+    fn remove(&mut self, row: Row<T>, item: &T) {
+        let key = self.compared((self.predicate)(item));
+        self.remove_key(row, &key);
+    }
+
+    fn stats(&self) -> IndexStats {
+        IndexStats {
+            keys: self.keys.len(),
+            rows: self.keys.values().map(Selection::len).sum(),
+        }
+    }
+
+    fn before_update(&mut self, row: Row<T>, item: &T) {
+        let key = self.compared((self.predicate)(item));
+        self.pending.stash(row, key);
+    }
+
+    fn after_update(&mut self, row: Row<T>, item: &T) {
+        match self.pending.take(row) {
+            Some(old) if old == self.compared((self.predicate)(item)) => {}
+            Some(old) => {
+                self.remove_key(row, &old);
+                self.add(row, item);
+            }
+            None => self.add(row, item),
+        }
+    }
+
+    fn before_update_many(&mut self, _items: &[(Row<T>, &T)]) {}
+
+    fn after_update_many(&mut self, items: &[(Row<T>, &T)]) {
+        let (rows, moved) = moved(items, |row, item| {
+            self.keys
+                .get(&self.compared((self.predicate)(item)))
+                .is_some_and(|selection| selection.contains(row))
+        });
+        if moved.is_empty() {
+            return;
+        }
+        self.keys.retain(|_, selection| {
+            *selection -= &rows;
+            !selection.is_empty()
+        });
+        self.add_many(&moved);
+    }
+}
+
+struct Compared<V> {
+    value: V,
+    compare: fn(&V, &V) -> Ordering,
+}
+
+impl<V> PartialEq for Compared<V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<V> Eq for Compared<V> {}
+
+impl<V> PartialOrd for Compared<V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<V> Ord for Compared<V> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.compare)(&self.value, &other.value)
+    }
+}
+
+// aggregate
+
+pub struct AggregateIndex<T, V, G = ()>
+where
+    G: Eq + Hash,
+{
+    group: fn(&T) -> G,
+    value: fn(&T) -> V,
+    totals: HashMap<G, (V, u64)>,
+}
+
+impl<T, V> AggregateIndex<T, V>
+where
+    V: Default + Copy + AddAssign + SubAssign,
+{
+    pub fn new(value: fn(&T) -> V) -> Self {
+        Self::grouped(|_| (), value)
+    }
+}
+
+impl<T, V, G> AggregateIndex<T, V, G>
+where
+    V: Default + Copy + AddAssign + SubAssign,
+    G: Eq + Hash,
+{
+    pub fn grouped(group: fn(&T) -> G, value: fn(&T) -> V) -> Self {
+        Self {
+            group,
+            value,
+            totals: HashMap::new(),
+        }
+    }
+
+    pub fn sum<Q>(&self, group: &Q) -> V
+    where
+        G: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.totals
+            .get(group)
+            .map(|&(sum, _)| sum)
+            .unwrap_or_default()
+    }
+
+    pub fn count<Q>(&self, group: &Q) -> u64
+    where
+        G: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.totals.get(group).map(|&(_, count)| count).unwrap_or(0)
+    }
+
+    pub fn total(&self) -> V {
+        let mut total = V::default();
+        for &(sum, _) in self.totals.values() {
+            total += sum;
+        }
+        total
+    }
+
+    pub fn groups(&self) -> impl Iterator<Item = (&G, V)> + '_ {
+        self.totals.iter().map(|(group, &(sum, _))| (group, sum))
+    }
+}
+
+impl<T, V, G> Index<T> for AggregateIndex<T, V, G>
+where
+    V: Default + Copy + AddAssign + SubAssign,
+    G: Eq + Hash,
+{
+    fn add(&mut self, _row: Row<T>, item: &T) {
+        let (sum, count) = self.totals.entry((self.group)(item)).or_default();
+        *sum += (self.value)(item);
+        *count += 1;
+    }
+
+    fn remove(&mut self, _row: Row<T>, item: &T) {
+        let group = (self.group)(item);
+        if let Some((sum, count)) = self.totals.get_mut(&group) {
+            *sum -= (self.value)(item);
+            *count -= 1;
+            if *count == 0 {
+                self.totals.remove(&group);
+            }
+        }
+    }
+
+    fn stats(&self) -> IndexStats {
+        IndexStats {
+            keys: self.totals.len(),
+            rows: self.totals.values().map(|&(_, count)| count).sum(),
+        }
+    }
+}
+
+// min/max
+
+pub struct MinMaxIndex<T, V>
+where
+    V: Ord,
+{
+    predicate: fn(&T) -> V,
+    counts: BTreeMap<V, u64>,
+    pending: Pending<T, V>,
+}
+
+impl<T, V> MinMaxIndex<T, V>
+where
+    V: Ord,
+{
+    pub fn new(predicate: fn(&T) -> V) -> Self {
+        Self {
+            predicate,
+            counts: BTreeMap::new(),
+            pending: Pending::default(),
+        }
+    }
+
+    pub fn min(&self) -> Option<&V> {
+        self.counts.keys().next()
+    }
+
+    pub fn max(&self) -> Option<&V> {
+        self.counts.keys().next_back()
+    }
+
+    fn remove_key(&mut self, key: &V) {
+        if let Some(count) = self.counts.get_mut(key) {
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(key);
+            }
+        }
+    }
+}
+
+impl<T, V> Index<T> for MinMaxIndex<T, V>
+where
+    V: Ord,
+{
+    fn add(&mut self, _row: Row<T>, item: &T) {
+        *self.counts.entry((self.predicate)(item)).or_insert(0) += 1;
+    }
+
+    fn remove(&mut self, _row: Row<T>, item: &T) {
+        self.remove_key(&(self.predicate)(item));
+    }
+
+    fn stats(&self) -> IndexStats {
+        IndexStats {
+            keys: self.counts.len(),
+            rows: self.counts.values().sum(),
+        }
+    }
+
+    fn before_update(&mut self, row: Row<T>, item: &T) {
+        self.pending.stash(row, (self.predicate)(item));
+    }
+
+    fn after_update(&mut self, row: Row<T>, item: &T) {
+        match self.pending.take(row) {
+            Some(old) if old == (self.predicate)(item) => {}
+            Some(old) => {
+                self.remove_key(&old);
+                self.add(row, item);
+            }
+            None => self.add(row, item),
+        }
+    }
+}
+
+// priority
+
+pub struct PriorityIndex<T, P>
+where
+    P: Ord,
+{
+    priority: fn(&T) -> P,
+    selections: BTreeMap<P, Selection<T>>,
+    pending: Pending<T, P>,
+}
+
+impl<T, P> PriorityIndex<T, P>
+where
+    P: Ord,
+{
+    pub fn new(priority: fn(&T) -> P) -> Self {
+        Self {
+            priority,
+            selections: BTreeMap::new(),
+            pending: Pending::default(),
+        }
+    }
+
+    pub fn lowest(&self) -> Option<Row<T>> {
+        self.selections
+            .values()
+            .next()
+            .and_then(|selection| selection.rows().next())
+    }
+
+    pub fn highest(&self) -> Option<Row<T>> {
+        self.selections
+            .values()
+            .next_back()
+            .and_then(|selection| selection.rows().next())
+    }
+
+    fn remove_key(&mut self, row: Row<T>, priority: &P) {
+        if let Some(selection) = self.selections.get_mut(priority) {
+            selection.remove(row);
+            if selection.is_empty() {
+                self.selections.remove(priority);
+            }
+        }
+    }
+}
+
+impl<T, P> Index<T> for PriorityIndex<T, P>
+where
+    P: Ord,
+{
+    fn add(&mut self, row: Row<T>, item: &T) {
+        self.selections
+            .entry((self.priority)(item))
+            .or_default()
+            .add(row);
+    }
+
+    fn remove(&mut self, row: Row<T>, item: &T) {
+        self.remove_key(row, &(self.priority)(item));
+    }
+
+    fn stats(&self) -> IndexStats {
+        IndexStats {
+            keys: self.selections.len(),
+            rows: self.selections.values().map(Selection::len).sum(),
+        }
+    }
+
+    fn before_update(&mut self, row: Row<T>, item: &T) {
+        self.pending.stash(row, (self.priority)(item));
+    }
+
+    fn after_update(&mut self, row: Row<T>, item: &T) {
+        match self.pending.take(row) {
+            Some(old) if old == (self.priority)(item) => {}
+            Some(old) => {
+                self.remove_key(row, &old);
+                self.add(row, item);
+            }
+            None => self.add(row, item),
+        }
+    }
+
+    fn before_update_many(&mut self, _items: &[(Row<T>, &T)]) {}
+
+    fn after_update_many(&mut self, items: &[(Row<T>, &T)]) {
+        let (rows, moved) = moved(items, |row, item| {
+            self.selections
+                .get(&(self.priority)(item))
+                .is_some_and(|selection| selection.contains(row))
+        });
+        if moved.is_empty() {
+            return;
+        }
+        self.selections.retain(|_, selection| {
+            *selection -= &rows;
+            !selection.is_empty()
+        });
+        self.add_many(&moved);
+    }
+}
+
+// quantiles
+
+pub struct QuantileIndex<T, V>
+where
+    V: Ord,
+{
+    predicate: fn(&T) -> V,
+    counts: BTreeMap<V, u64>,
+    values: Vec<Option<V>>,
+    len: u64,
+}
+
+impl<T, V> QuantileIndex<T, V>
+where
+    V: Ord + Copy,
+{
+    pub fn new(predicate: fn(&T) -> V) -> Self {
+        Self {
+            predicate,
+            counts: BTreeMap::new(),
+            values: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn quantile(&self, quantile: f64) -> Option<V> {
+        let rank = nearest_rank(quantile, self.len)?;
+        let mut seen = 0;
+        for (&value, &count) in &self.counts {
+            seen += count;
+            if seen >= rank {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    pub fn quantile_of(&self, selection: &Selection<T>, quantile: f64) -> Option<V> {
+        let mut values: Vec<V> = selection
+            .rows()
+            .filter_map(|row| self.values.get(row.as_index()).copied().flatten())
+            .collect();
+        let rank = nearest_rank(quantile, values.len() as u64)?;
+        let (_, &mut value, _) = values.select_nth_unstable(rank as usize - 1);
+        Some(value)
+    }
+}
+
+impl<T, V> Index<T> for QuantileIndex<T, V>
+where
+    V: Ord + Copy,
+{
+    fn add(&mut self, row: Row<T>, item: &T) {
+        let value = (self.predicate)(item);
+        if self.values.len() <= row.as_index() {
+            self.values.resize(row.as_index() + 1, None);
+        }
+        if let Some(previous) = self.values[row.as_index()].replace(value) {
+            self.forget(previous);
+        }
+        *self.counts.entry(value).or_insert(0) += 1;
+        self.len += 1;
+    }
+
+    fn remove(&mut self, row: Row<T>, _item: &T) {
+        if let Some(value) = self.values.get_mut(row.as_index()).and_then(Option::take) {
+            self.forget(value);
+        }
+    }
+
+    fn stats(&self) -> IndexStats {
+        IndexStats {
+            keys: self.counts.len(),
+            rows: self.len,
+        }
+    }
+}
+
+impl<T, V> QuantileIndex<T, V>
+where
+    V: Ord + Copy,
+{
+    fn forget(&mut self, value: V) {
+        if let Some(count) = self.counts.get_mut(&value) {
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(&value);
+            }
+            self.len -= 1;
+        }
+    }
+}
+
+fn nearest_rank(quantile: f64, len: u64) -> Option<u64> {
+    if len == 0 {
+        return None;
+    }
+    let rank = (quantile.clamp(0.0, 1.0) * len as f64).ceil() as u64;
+    Some(rank.max(1))
+}
+
+// insertion order
+
+pub struct InsertionOrderIndex<T> {
+    next: u64,
+    rows: BTreeMap<u64, Row<T>>,
+    sequences: HashMap<Row<T>, u64>,
+}
+
+impl<T> Default for InsertionOrderIndex<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> InsertionOrderIndex<T> {
+    pub fn new() -> Self {
+        Self {
+            next: 0,
+            rows: BTreeMap::new(),
+            sequences: HashMap::new(),
+        }
+    }
+
+    pub fn sequence(&self, row: Row<T>) -> Option<u64> {
+        self.sequences.get(&row).copied()
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = Row<T>> + '_ {
+        self.rows.values().copied()
+    }
+
+    pub fn ordered(&self, selection: &Selection<T>) -> Vec<Row<T>> {
+        self.rows().filter(|&row| selection.contains(row)).collect()
+    }
+}
+
+impl<T> Index<T> for InsertionOrderIndex<T> {
+    fn add(&mut self, row: Row<T>, _item: &T) {
+        self.rows.insert(self.next, row);
+        self.sequences.insert(row, self.next);
+        self.next += 1;
+    }
+
+    fn remove(&mut self, row: Row<T>, _item: &T) {
+        if let Some(sequence) = self.sequences.remove(&row) {
+            self.rows.remove(&sequence);
+        }
+    }
+
+    fn stats(&self) -> IndexStats {
+        IndexStats {
+            keys: self.rows.len(),
+            rows: self.rows.len() as u64,
+        }
+    }
+
+    fn before_update(&mut self, _row: Row<T>, _item: &T) {}
+
+    fn after_update(&mut self, _row: Row<T>, _item: &T) {}
+
+    fn before_update_many(&mut self, _items: &[(Row<T>, &T)]) {}
+
+    fn after_update_many(&mut self, _items: &[(Row<T>, &T)]) {}
+}
+
+// window
+
+pub struct WindowIndex<T> {
+    predicate: fn(&T) -> SystemTime,
+    length: Duration,
+    times: BTreeMap<SystemTime, Selection<T>>,
+    end: Option<SystemTime>,
+    current: Selection<T>,
+}
+
+impl<T> WindowIndex<T> {
+    pub fn new(predicate: fn(&T) -> SystemTime, length: Duration) -> Self {
+        Self {
+            predicate,
+            length,
+            times: BTreeMap::new(),
+            end: None,
+            current: Selection::empty(),
+        }
+    }
+
+    pub fn in_window(&self, now: SystemTime, length: Duration) -> Selection<T> {
+        self.between(start_of(now, length), now)
+    }
+
+    pub fn current(&self) -> &Selection<T> {
+        &self.current
+    }
+
+    pub fn advance(&mut self, now: SystemTime) {
+        match self.end {
+            Some(end) if end <= now => {
+                let added = self.between(end, now);
+                let expired = self.between(start_of(end, self.length), start_of(now, self.length));
+                self.current |= &added;
+                self.current -= &expired;
+            }
+            _ => self.current = self.in_window(now, self.length),
+        }
+        self.end = Some(now);
+    }
+
+    fn between(&self, after: SystemTime, until: SystemTime) -> Selection<T> {
+        let mut result = Selection::empty();
+        if after < until {
+            for (_, selection) in self.times.range((Excluded(after), Included(until))) {
+                result |= selection;
+            }
+        }
+        result
+    }
+
+    fn contains(&self, time: SystemTime) -> bool {
+        match self.end {
+            Some(end) => time > start_of(end, self.length) && time <= end,
+            None => false,
+        }
+    }
+}
+
+impl<T> Index<T> for WindowIndex<T> {
+    fn add(&mut self, row: Row<T>, item: &T) {
+        let time = (self.predicate)(item);
+        self.times.entry(time).or_default().add(row);
+        if self.contains(time) {
+            self.current.add(row);
+        }
+    }
+
+    fn remove(&mut self, row: Row<T>, item: &T) {
+        let time = (self.predicate)(item);
+        if let Some(selection) = self.times.get_mut(&time) {
+            selection.remove(row);
+            if selection.is_empty() {
+                self.times.remove(&time);
+            }
+        }
+        self.current.remove(row);
+    }
+
+    fn stats(&self) -> IndexStats {
+        IndexStats {
+            keys: self.times.len(),
+            rows: self.times.values().map(Selection::len).sum(),
+        }
+    }
+}
+
+fn start_of(end: SystemTime, length: Duration) -> SystemTime {
+    end.checked_sub(length).unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+// validity
+
+pub struct ValidityIndex<T, V>
+where
+    V: Ord,
+{
+    valid_from: fn(&T) -> &V,
+    valid_to: fn(&T) -> Option<&V>,
+    starts: BTreeMap<V, Selection<T>>,
+    ends: BTreeMap<V, Selection<T>>,
+    pending: Pending<T, (V, Option<V>)>,
+}
+
+impl<T, V> ValidityIndex<T, V>
+where
+    V: Ord,
+{
+    pub fn new(valid_from: fn(&T) -> &V, valid_to: fn(&T) -> Option<&V>) -> Self {
+        Self {
+            valid_from,
+            valid_to,
+            starts: BTreeMap::new(),
+            ends: BTreeMap::new(),
+            pending: Pending::default(),
+        }
+    }
+
+    pub fn valid_at(&self, at: &V) -> Selection<T> {
+        let mut result = Selection::empty();
+        for (_, selection) in self.starts.range(..=at) {
+            result |= selection;
+        }
+        for (_, selection) in self.ends.range(..=at) {
+            result -= selection;
+        }
+        result
+    }
+}
+
+impl<T, V> Index<T> for ValidityIndex<T, V>
+where
+    V: Ord + Clone,
+{
+    fn add(&mut self, row: Row<T>, item: &T) {
+        add_to(&mut self.starts, (self.valid_from)(item), row);
+        if let Some(end) = (self.valid_to)(item) {
+            add_to(&mut self.ends, end, row);
+        }
+    }
+
+    fn remove(&mut self, row: Row<T>, item: &T) {
+        remove_from(&mut self.starts, (self.valid_from)(item), row);
+        if let Some(end) = (self.valid_to)(item) {
+            remove_from(&mut self.ends, end, row);
+        }
+    }
+
+    fn stats(&self) -> IndexStats {
+        IndexStats {
+            keys: self.starts.len(),
+            rows: self.starts.values().map(Selection::len).sum(),
+        }
+    }
+
+    fn before_update(&mut self, row: Row<T>, item: &T) {
+        let from = (self.valid_from)(item).clone();
+        let to = (self.valid_to)(item).cloned();
+        self.pending.stash(row, (from, to));
+    }
+
+    fn after_update(&mut self, row: Row<T>, item: &T) {
+        let (old_from, old_to) = match self.pending.take(row) {
+            Some(old) => old,
+            None => return self.add(row, item),
+        };
+        let new_from = (self.valid_from)(item);
+        if old_from != *new_from {
+            remove_from(&mut self.starts, &old_from, row);
+            add_to(&mut self.starts, new_from, row);
+        }
+        let new_to = (self.valid_to)(item);
+        if old_to.as_ref() != new_to {
+            if let Some(end) = &old_to {
+                remove_from(&mut self.ends, end, row);
+            }
+            if let Some(end) = new_to {
+                add_to(&mut self.ends, end, row);
+            }
+        }
+    }
+}
+
+fn add_to<T, V: Ord + Clone>(map: &mut BTreeMap<V, Selection<T>>, key: &V, row: Row<T>) {
+    match map.get_mut(key) {
+        Some(selection) => selection.add(row),
+        None => {
+            map.insert(key.clone(), Selection::of_row(row));
+        }
+    }
+}
+
+fn remove_from<T, V: Ord>(map: &mut BTreeMap<V, Selection<T>>, key: &V, row: Row<T>) {
+    if let Some(selection) = map.get_mut(key) {
+        selection.remove(row);
+        if selection.is_empty() {
+            map.remove(key);
+        }
+    }
+}
+
+// prefix & suffix
+
+struct StringKeys<T> {
+    selections: BTreeMap<String, Selection<T>>,
+    normalize: Option<fn(&str) -> String>,
+}
+
+impl<T> StringKeys<T> {
+    fn new(normalize: Option<fn(&str) -> String>) -> Self {
+        Self {
+            selections: BTreeMap::new(),
+            normalize,
+        }
+    }
+
+    fn normalized<'value>(&self, value: &'value str) -> Cow<'value, str> {
+        match self.normalize {
+            Some(normalize) => Cow::Owned(normalize(value)),
+            None => Cow::Borrowed(value),
+        }
+    }
+
+    fn add(&mut self, key: String, row: Row<T>) {
+        self.selections.entry(key).or_default().add(row);
+    }
+
+    fn remove(&mut self, key: &str, row: Row<T>) {
+        if let Some(selection) = self.selections.get_mut(key) {
+            selection.remove(row);
+            if selection.is_empty() {
+                self.selections.remove(key);
+            }
+        }
+    }
+
+    fn prefixed(&self, prefix: &str) -> Selection<T> {
+        let mut result = Selection::empty();
+        for (_, selection) in self.completions(prefix) {
+            result |= selection;
+        }
+        result
+    }
+
+    fn completions<'keys: 'prefix, 'prefix>(
+        &'keys self,
+        prefix: &'prefix str,
+    ) -> impl Iterator<Item = (&'keys String, &'keys Selection<T>)> + 'prefix {
+        self.selections
+            .range::<str, _>((Included(prefix), Unbounded))
+            .take_while(move |(key, _)| key.starts_with(prefix))
+    }
+
+    fn stats(&self) -> IndexStats {
+        IndexStats {
+            keys: self.selections.len(),
+            rows: self.selections.values().map(Selection::len).sum(),
+        }
+    }
+}
+
+pub struct PrefixIndex<T> {
+    predicate: fn(&T) -> &str,
+    keys: StringKeys<T>,
+}
+
+impl<T> PrefixIndex<T> {
+    pub fn new(predicate: fn(&T) -> &str) -> Self {
+        Self {
+            predicate,
+            keys: StringKeys::new(None),
+        }
+    }
+
+    pub fn normalized(predicate: fn(&T) -> &str, normalize: fn(&str) -> String) -> Self {
+        Self {
+            predicate,
+            keys: StringKeys::new(Some(normalize)),
+        }
+    }
+
+    pub fn starts_with(&self, prefix: &str) -> Selection<T> {
+        self.keys.prefixed(&self.keys.normalized(prefix))
+    }
+
+    pub fn suggest(&self, prefix: &str, limit: usize) -> Vec<(&str, u64)> {
+        self.suggest_weighted(prefix, limit, |_, selection| selection.len())
+    }
+
+    pub fn suggest_weighted<W, F>(&self, prefix: &str, limit: usize, weight: F) -> Vec<(&str, W)>
+    where
+        W: Ord,
+        F: Fn(&str, &Selection<T>) -> W,
+    {
+        let prefix = self.keys.normalized(prefix);
+        let mut suggestions: Vec<_> = self
+            .keys
+            .completions(&prefix)
+            .map(|(key, selection)| (key.as_str(), weight(key, selection)))
+            .collect();
+        suggestions.sort_by(|(a_key, a_weight), (b_key, b_weight)| {
+            b_weight.cmp(a_weight).then_with(|| a_key.cmp(b_key))
+        });
+        suggestions.truncate(limit);
+        suggestions
+    }
+}
+
+impl<T> Index<T> for PrefixIndex<T> {
+    fn add(&mut self, row: Row<T>, item: &T) {
+        let key = self.keys.normalized((self.predicate)(item)).into_owned();
+        self.keys.add(key, row);
+    }
+
+    fn remove(&mut self, row: Row<T>, item: &T) {
+        let key = self.keys.normalized((self.predicate)(item)).into_owned();
+        self.keys.remove(&key, row);
+    }
+
+    fn stats(&self) -> IndexStats {
+        self.keys.stats()
+    }
+}
+
+pub struct SuffixIndex<T> {
+    predicate: fn(&T) -> &str,
+    keys: StringKeys<T>,
+}
+
+impl<T> SuffixIndex<T> {
+    pub fn new(predicate: fn(&T) -> &str) -> Self {
+        Self {
+            predicate,
+            keys: StringKeys::new(None),
+        }
+    }
+
+    pub fn normalized(predicate: fn(&T) -> &str, normalize: fn(&str) -> String) -> Self {
+        Self {
+            predicate,
+            keys: StringKeys::new(Some(normalize)),
+        }
+    }
+
+    pub fn ends_with(&self, suffix: &str) -> Selection<T> {
+        self.keys.prefixed(&reversed(&self.keys.normalized(suffix)))
+    }
+}
+
+impl<T> Index<T> for SuffixIndex<T> {
+    fn add(&mut self, row: Row<T>, item: &T) {
+        let key = reversed(&self.keys.normalized((self.predicate)(item)));
+        self.keys.add(key, row);
+    }
+
+    fn remove(&mut self, row: Row<T>, item: &T) {
+        let key = reversed(&self.keys.normalized((self.predicate)(item)));
+        self.keys.remove(&key, row);
+    }
+
+    fn stats(&self) -> IndexStats {
+        self.keys.stats()
+    }
+}
+
+fn reversed(value: &str) -> String {
+    value.chars().rev().collect()
+}
+
+// fuzzy
+
+pub struct FuzzyIndex<T> {
+    predicate: fn(&T) -> &str,
+    root: Option<FuzzyNode<T>>,
+}
+
+struct FuzzyNode<T> {
+    key: String,
+    selection: Selection<T>,
+    children: HashMap<usize, FuzzyNode<T>>,
+}
+
+impl<T> FuzzyNode<T> {
+    fn new(key: &str, row: Row<T>) -> Self {
+        Self {
+            key: key.to_string(),
+            selection: Selection::of_row(row),
+            children: HashMap::new(),
+        }
+    }
+}
+
+impl<T> FuzzyIndex<T> {
+    pub fn new(predicate: fn(&T) -> &str) -> Self {
+        Self {
+            predicate,
+            root: None,
+        }
+    }
+
+    pub fn matches_within(&self, value: &str, max_distance: usize) -> Selection<T> {
+        let mut result = Selection::empty();
+        let mut pending: Vec<&FuzzyNode<T>> = self.root.iter().collect();
+        while let Some(node) = pending.pop() {
+            let distance = edit_distance(&node.key, value);
+            if distance <= max_distance {
+                result |= &node.selection;
+            }
+            let nearest = distance.saturating_sub(max_distance);
+            let farthest = distance + max_distance;
+            pending.extend(
+                node.children
+                    .iter()
+                    .filter(|(&d, _)| d >= nearest && d <= farthest)
+                    .map(|(_, child)| child),
+            );
+        }
+        result
+    }
+}
+
+impl<T> Index<T> for FuzzyIndex<T> {
+    fn add(&mut self, row: Row<T>, item: &T) {
+        let key = (self.predicate)(item);
+        let mut node = match &mut self.root {
+            Some(root) => root,
+            None => {
+                self.root = Some(FuzzyNode::new(key, row));
+                return;
+            }
+        };
+        loop {
+            let distance = edit_distance(&node.key, key);
+            if distance == 0 {
+                node.selection.add(row);
+                return;
+            }
+            node = node
+                .children
+                .entry(distance)
+                .or_insert_with(|| FuzzyNode::new(key, row));
+        }
+    }
+
+    fn remove(&mut self, row: Row<T>, item: &T) {
+        let key = (self.predicate)(item);
+        let mut next = self.root.as_mut();
+        while let Some(node) = next {
+            let distance = edit_distance(&node.key, key);
+            if distance == 0 {
+                node.selection.remove(row);
+                return;
+            }
+            next = node.children.get_mut(&distance);
+        }
+    }
+
+    fn stats(&self) -> IndexStats {
+        let mut stats = IndexStats::default();
+        let mut pending: Vec<&FuzzyNode<T>> = self.root.iter().collect();
+        while let Some(node) = pending.pop() {
+            if !node.selection.is_empty() {
+                stats.keys += 1;
+                stats.rows += node.selection.len();
+            }
+            pending.extend(node.children.values());
+        }
+        stats
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, &b) in b.iter().enumerate() {
+            let substitution = previous[j] + (a != b) as usize;
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+// phonetic
+
+pub struct PhoneticIndex<T> {
+    predicate: fn(&T) -> &str,
+    selections: HashMap<String, Selection<T>>,
+    empty: Selection<T>,
+}
+
+impl<T> PhoneticIndex<T> {
+    pub fn new(predicate: fn(&T) -> &str) -> Self {
+        Self {
+            predicate,
+            selections: HashMap::new(),
+            empty: Selection::empty(),
+        }
+    }
+
+    pub fn sounds_like(&self, value: &str) -> &Selection<T> {
+        soundex(value)
+            .and_then(|code| self.selections.get(&code))
+            .unwrap_or(&self.empty)
+    }
+}
+
+impl<T> Index<T> for PhoneticIndex<T> {
+    fn add(&mut self, row: Row<T>, item: &T) {
+        if let Some(code) = soundex((self.predicate)(item)) {
+            self.selections.entry(code).or_default().add(row);
+        }
+    }
+
+    fn remove(&mut self, row: Row<T>, item: &T) {
+        let code = match soundex((self.predicate)(item)) {
+            Some(code) => code,
+            None => return,
+        };
+        if let Some(selection) = self.selections.get_mut(&code) {
+            selection.remove(row);
+            if selection.is_empty() {
+                self.selections.remove(&code);
+            }
+        }
+    }
+
+    fn stats(&self) -> IndexStats {
+        IndexStats {
+            keys: self.selections.len(),
+            rows: self.selections.values().map(Selection::len).sum(),
+        }
+    }
+}
+
+fn soundex(value: &str) -> Option<String> {
+    let mut letters = value
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_lowercase());
+
+    let first = letters.next()?;
+
+    let mut code = first.to_ascii_uppercase().to_string();
+    let mut last = soundex_digit(first);
+    for letter in letters {
+        match soundex_digit(letter) {
+            None => {}
+            Some(digit) if digit == '0' || last == Some(digit) => last = Some(digit),
+            Some(digit) => {
+                code.push(digit);
+                if code.len() == 4 {
+                    break;
+                }
+                last = Some(digit);
+            }
+        }
+    }
+
+    while code.len() < 4 {
+        code.push('0');
+    }
+    Some(code)
+}
+
+fn soundex_digit(letter: char) -> Option<char> {
+    match letter {
+        'h' | 'w' => None,
+        'b' | 'f' | 'p' | 'v' => Some('1'),
+        'c' | 'g' | 'j' | 'k' | 'q' | 's' | 'x' | 'z' => Some('2'),
+        'd' | 't' => Some('3'),
+        'l' => Some('4'),
+        'm' | 'n' => Some('5'),
+        'r' => Some('6'),
+        _ => Some('0'),
+    }
+}
+
+// boolean
+
+pub struct BooleanIndex<T> {
+    predicate: fn(&T) -> bool,
+    selection: Selection<T>,
+    complement: Selection<T>,
+}
+
+impl<T> BooleanIndex<T> {
+    pub fn new(predicate: fn(&T) -> bool) -> Self {
+        Self {
+            predicate,
+            selection: Selection::empty(),
+            complement: Selection::empty(),
+        }
+    }
+
+    pub fn get(&self) -> &Selection<T> {
+        &self.selection
+    }
+
+    pub fn get_false(&self) -> &Selection<T> {
+        &self.complement
+    }
+}
+
+impl<T> Index<T> for BooleanIndex<T> {
+    fn add(&mut self, row: Row<T>, item: &T) {
+        if (self.predicate)(item) {
+            self.selection.add(row);
+        } else {
+            self.complement.add(row);
+        }
+    }
+
+    fn remove(&mut self, row: Row<T>, _item: &T) {
         self.selection.remove(row);
+        self.complement.remove(row);
+    }
+
+    fn stats(&self) -> IndexStats {
+        IndexStats {
+            keys: !self.selection.is_empty() as usize,
+            rows: self.selection.len(),
+        }
+    }
+
+    fn before_update(&mut self, _row: Row<T>, _item: &T) {}
+
+    fn after_update(&mut self, row: Row<T>, item: &T) {
+        self.remove(row, item);
+        self.add(row, item);
+    }
+
+    fn add_many(&mut self, items: &[(Row<T>, &T)]) {
+        let (matching, other): (Vec<_>, Vec<_>) =
+            items.iter().partition(|(_, item)| (self.predicate)(item));
+        let batch: Selection<T> = matching.into_iter().map(|&(row, _)| row).collect();
+        self.selection |= &batch;
+        let batch: Selection<T> = other.into_iter().map(|&(row, _)| row).collect();
+        self.complement |= &batch;
+    }
+
+    fn remove_many(&mut self, items: &[(Row<T>, &T)]) {
+        let batch: Selection<T> = items.iter().map(|&(row, _)| row).collect();
+        self.selection -= &batch;
+        self.complement -= &batch;
+    }
+
+    fn before_update_many(&mut self, _items: &[(Row<T>, &T)]) {}
+
+    fn after_update_many(&mut self, items: &[(Row<T>, &T)]) {
+        let (matching, other): (Vec<_>, Vec<_>) =
+            items.iter().partition(|(_, item)| (self.predicate)(item));
+        let now_true: Selection<T> = matching.into_iter().map(|&(row, _)| row).collect();
+        let now_false: Selection<T> = other.into_iter().map(|&(row, _)| row).collect();
+        self.selection -= &now_false;
+        self.complement -= &now_true;
+        self.selection |= &now_true;
+        self.complement |= &now_false;
+    }
+
+    fn warm(&mut self) {
+        self.selection.optimize();
+        self.complement.optimize();
+    }
+}
+
+// tree
+
+pub struct TreeIndex<T, V>
+where
+    V: Eq + Hash,
+{
+    id: fn(&T) -> V,
+    parent: fn(&T) -> Option<V>,
+    rows: HashMap<V, Row<T>>,
+    nodes: HashMap<usize, (V, Option<V>)>,
+    children: HashMap<V, Selection<T>>,
+    empty: Selection<T>,
+}
+
+impl<T, V> TreeIndex<T, V>
+where
+    V: Eq + Hash + Clone,
+{
+    pub fn new(id: fn(&T) -> V, parent: fn(&T) -> Option<V>) -> Self {
+        Self {
+            id,
+            parent,
+            rows: HashMap::new(),
+            nodes: HashMap::new(),
+            children: HashMap::new(),
+            empty: Selection::empty(),
+        }
+    }
+
+    pub fn parent_of(&self, row: Row<T>) -> Option<Row<T>> {
+        let (_, parent) = self.nodes.get(&row.as_index())?;
+        self.rows.get(parent.as_ref()?).copied()
+    }
+
+    pub fn children_of(&self, row: Row<T>) -> &Selection<T> {
+        self.nodes
+            .get(&row.as_index())
+            .and_then(|(id, _)| self.children.get(id))
+            .unwrap_or(&self.empty)
+    }
+
+    pub fn descendants_of(&self, row: Row<T>) -> Selection<T> {
+        let mut descendants = Selection::empty();
+        let mut pending = vec![row];
+        while let Some(row) = pending.pop() {
+            for child in self.children_of(row).rows() {
+                if !descendants.contains(child) {
+                    descendants.add(child);
+                    pending.push(child);
+                }
+            }
+        }
+        descendants
+    }
+
+    pub fn ancestors_of(&self, row: Row<T>) -> Selection<T> {
+        let mut ancestors = Selection::empty();
+        let mut next = self.parent_of(row);
+        while let Some(parent) = next {
+            if ancestors.contains(parent) {
+                break;
+            }
+            ancestors.add(parent);
+            next = self.parent_of(parent);
+        }
+        ancestors
+    }
+}
+
+impl<T, V> Index<T> for TreeIndex<T, V>
+where
+    V: Eq + Hash + Clone,
+{
+    fn add(&mut self, row: Row<T>, item: &T) {
+        let id = (self.id)(item);
+        let parent = (self.parent)(item);
+        if let Some(parent) = &parent {
+            self.children.entry(parent.clone()).or_default().add(row);
+        }
+        self.rows.insert(id.clone(), row);
+        self.nodes.insert(row.as_index(), (id, parent));
+    }
+
+    fn remove(&mut self, row: Row<T>, _item: &T) {
+        if let Some((id, parent)) = self.nodes.remove(&row.as_index()) {
+            if let Some(parent) = parent {
+                if let Some(selection) = self.children.get_mut(&parent) {
+                    selection.remove(row);
+                    if selection.is_empty() {
+                        self.children.remove(&parent);
+                    }
+                }
+            }
+            if self.rows.get(&id) == Some(&row) {
+                self.rows.remove(&id);
+            }
+        }
+    }
+
+    fn stats(&self) -> IndexStats {
+        IndexStats {
+            keys: self.rows.len(),
+            rows: self.nodes.len() as u64,
+        }
+    }
+}
+
+// edges
+
+pub struct EdgeIndex<A, B, K = ()>
+where
+    K: Eq + Hash,
+{
+    edges: HashMap<K, HashMap<usize, Selection<B>>>,
+    empty: Selection<B>,
+    _marker: PhantomData<A>,
+}
+
+impl<A, B, K> EdgeIndex<A, B, K>
+where
+    K: Eq + Hash,
+{
+    pub fn new() -> Self {
+        Self {
+            edges: HashMap::new(),
+            empty: Selection::empty(),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn connect(&mut self, kind: K, from: Row<A>, to: Row<B>) {
+        self.edges
+            .entry(kind)
+            .or_default()
+            .entry(from.as_index())
+            .or_default()
+            .add(to);
+    }
+
+    pub fn disconnect(&mut self, kind: &K, from: Row<A>, to: Row<B>) {
+        let targets = self
+            .edges
+            .get_mut(kind)
+            .and_then(|edges| edges.get_mut(&from.as_index()));
+        if let Some(targets) = targets {
+            targets.remove(to);
+        }
+    }
+
+    pub fn neighbors(&self, kind: &K, from: Row<A>) -> &Selection<B> {
+        self.edges
+            .get(kind)
+            .and_then(|edges| edges.get(&from.as_index()))
+            .unwrap_or(&self.empty)
+    }
+
+    pub fn neighbors_of(&self, kind: &K, from: &Selection<A>) -> Selection<B> {
+        let mut result = Selection::empty();
+        for row in from.rows() {
+            result |= self.neighbors(kind, row);
+        }
+        result
+    }
+}
+
+impl<T, K> EdgeIndex<T, T, K>
+where
+    K: Eq + Hash,
+{
+    pub fn reachable_within(&self, kind: &K, from: &Selection<T>, hops: usize) -> Selection<T> {
+        let mut reached = Selection::empty();
+        let mut visited = from.clone();
+        let mut frontier = from.clone();
+        for _ in 0..hops {
+            let next = self.neighbors_of(kind, &frontier);
+            reached |= &next;
+            frontier = &next - &visited;
+            if frontier.is_empty() {
+                break;
+            }
+            visited |= &next;
+        }
+        reached
+    }
+}
+
+impl<A, B, K> Default for EdgeIndex<A, B, K>
+where
+    K: Eq + Hash,
+{
+    fn default() -> Self {
+        Self::new()
     }
 }
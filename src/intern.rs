@@ -0,0 +1,37 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+#[derive(Clone, Debug, Default)]
+pub struct Interner {
+    strings: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&mut self, value: &str) -> Arc<str> {
+        match self.strings.get(value) {
+            Some(interned) => interned.clone(),
+            None => {
+                let interned: Arc<str> = Arc::from(value);
+                self.strings.insert(interned.clone());
+                interned
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    pub fn shrink(&mut self) {
+        self.strings
+            .retain(|interned| Arc::strong_count(interned) > 1);
+    }
+}
@@ -1,4 +1,15 @@
+#[macro_use]
+mod macros;
+
+mod access;
+pub mod aggregate;
+mod capped;
+pub mod format;
 pub mod index;
+pub mod intern;
+pub mod metrics;
+pub mod partition;
+mod sample;
 pub mod selection;
 pub mod table;
 
@@ -0,0 +1,44 @@
+#[macro_export]
+macro_rules! query {
+    ($table:expr, $($terms:tt)+) => {{
+        let table = &$table;
+        let mut selection = $crate::selection::Selection::empty();
+        $crate::__query_terms!(table selection [] $($terms)+);
+        let mut query = table.select();
+        query.only(selection);
+        query
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __query_terms {
+    ($table:ident $selection:ident [$($calls:tt)*]) => {
+        let mut query = $table.select();
+        query $($calls)*;
+        $selection |= query.selection();
+    };
+    ($table:ident $selection:ident [$($calls:tt)*] || $($rest:tt)+) => {
+        $crate::__query_terms!($table $selection [$($calls)*]);
+        $crate::__query_terms!($table $selection [] $($rest)+);
+    };
+    ($table:ident $selection:ident [$($calls:tt)*] && $($rest:tt)+) => {
+        $crate::__query_terms!($table $selection [$($calls)*] $($rest)+);
+    };
+    ($table:ident $selection:ident [$($calls:tt)*] $name:ident == $value:tt $($rest:tt)*) => {
+        $crate::__query_terms!($table $selection [$($calls)* .$name($value)] $($rest)*);
+    };
+    ($table:ident $selection:ident [$($calls:tt)*] $name:ident ($($args:tt)*) $($rest:tt)*) => {
+        $crate::__query_terms!($table $selection [$($calls)* .$name($($args)*)] $($rest)*);
+    };
+    ($table:ident $selection:ident [$($calls:tt)*] $name:ident $($rest:tt)*) => {
+        $crate::__query_terms!($table $selection [$($calls)* .$name()] $($rest)*);
+    };
+}
+
+#[macro_export]
+macro_rules! select_fields {
+    ($query:expr, $($field:ident),+ $(,)?) => {
+        $query.project(|item| ($(item.$field.clone(),)+))
+    };
+}
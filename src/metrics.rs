@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+pub trait Metrics {
+    fn inserted(&self, _duration: Duration) {}
+    fn updated(&self, _rows: u64, _duration: Duration) {}
+    fn queried(&self, _rows: u64, _duration: Duration) {}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlowQuery {
+    pub rows: u64,
+    pub duration: Duration,
+}
+
+pub struct SlowQueries<F>
+where
+    F: Fn(SlowQuery),
+{
+    max_duration: Duration,
+    max_rows: u64,
+    hook: F,
+}
+
+impl<F> SlowQueries<F>
+where
+    F: Fn(SlowQuery),
+{
+    pub fn new(max_duration: Duration, max_rows: u64, hook: F) -> Self {
+        Self {
+            max_duration,
+            max_rows,
+            hook,
+        }
+    }
+}
+
+impl<F> Metrics for SlowQueries<F>
+where
+    F: Fn(SlowQuery),
+{
+    fn queried(&self, rows: u64, duration: Duration) {
+        if duration > self.max_duration || rows > self.max_rows {
+            (self.hook)(SlowQuery { rows, duration });
+        }
+    }
+}
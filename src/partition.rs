@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+use std::mem;
+use std::ops::RangeBounds;
+
+use crate::selection::Row;
+use crate::table::{Query, Selectable, Table};
+
+pub struct PartitionedTable<T, K>
+where
+    T: Selectable,
+    K: Ord,
+{
+    partition: fn(&T) -> K,
+    partitions: BTreeMap<K, Table<T>>,
+}
+
+impl<T, K> PartitionedTable<T, K>
+where
+    T: Selectable,
+    K: Ord + Clone,
+{
+    pub fn new(partition: fn(&T) -> K) -> Self {
+        Self {
+            partition,
+            partitions: BTreeMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, item: T) -> (K, Row<T>) {
+        let key = (self.partition)(&item);
+        let row = self
+            .partitions
+            .entry(key.clone())
+            .or_insert_with(Table::in_memory)
+            .insert(item);
+        (key, row)
+    }
+
+    pub fn partition(&self, key: &K) -> Option<&Table<T>> {
+        self.partitions.get(key)
+    }
+
+    pub fn partition_mut(&mut self, key: &K) -> Option<&mut Table<T>> {
+        self.partitions.get_mut(key)
+    }
+
+    pub fn drop_partition(&mut self, key: &K) -> Option<Table<T>> {
+        self.partitions.remove(key)
+    }
+
+    pub fn drop_partitions_before(&mut self, key: &K) -> usize {
+        let kept = self.partitions.split_off(key);
+        let dropped = mem::replace(&mut self.partitions, kept);
+        dropped.len()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> + '_ {
+        self.partitions.keys()
+    }
+
+    pub fn select<R>(&self, range: R) -> impl Iterator<Item = (&K, Query<T, &Table<T>>)> + '_
+    where
+        R: RangeBounds<K>,
+    {
+        self.partitions
+            .range(range)
+            .map(|(key, table)| (key, table.select()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.partitions.values().map(Table::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.partitions.values().all(Table::is_empty)
+    }
+}
@@ -0,0 +1,61 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use crate::selection::{Row, Selection};
+
+pub(crate) struct Reservoir<T> {
+    size: usize,
+    seen: u64,
+    rows: Vec<Row<T>>,
+    state: u64,
+}
+
+impl<T> Clone for Reservoir<T> {
+    fn clone(&self) -> Self {
+        Self {
+            size: self.size,
+            seen: self.seen,
+            rows: self.rows.clone(),
+            state: self.state,
+        }
+    }
+}
+
+impl<T> Reservoir<T> {
+    pub(crate) fn new(size: usize) -> Self {
+        Self {
+            size,
+            seen: 0,
+            rows: Vec::with_capacity(size),
+            state: RandomState::new().build_hasher().finish() | 1,
+        }
+    }
+
+    pub(crate) fn emptied(&self) -> Self {
+        Self::new(self.size)
+    }
+
+    pub(crate) fn offer(&mut self, row: Row<T>) {
+        self.seen += 1;
+        if self.rows.len() < self.size {
+            self.rows.push(row);
+        } else {
+            let slot = self.next_random() % self.seen;
+            if let Some(sampled) = self.rows.get_mut(slot as usize) {
+                *sampled = row;
+            }
+        }
+    }
+
+    pub(crate) fn selection(&self) -> Selection<T> {
+        self.rows.iter().copied().collect()
+    }
+
+    fn next_random(&mut self) -> u64 {
+        // xorshift64*
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
@@ -1,10 +1,12 @@
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
 use std::iter::FromIterator;
 use std::marker::{Copy, PhantomData};
-use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign};
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Sub, SubAssign};
 
 use croaring::Bitmap;
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug)]
 pub struct Row<T> {
     value: u32,
     _marker: PhantomData<T>,
@@ -18,7 +20,36 @@ impl<T> Clone for Row<T> {
     }
 }
 
+impl<T> PartialEq for Row<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T> Eq for Row<T> {}
+
+impl<T> PartialOrd for Row<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Row<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.value.cmp(&other.value)
+    }
+}
+
+impl<T> Hash for Row<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state);
+    }
+}
+
 impl<T> Row<T> {
+    /// # Safety
+    ///
+    /// The index must point at an existing item of the table the row is used with.
     pub unsafe fn from_index(value: usize) -> Self {
         Row {
             value: value as u32,
@@ -42,12 +73,18 @@ impl<T> Row<T> {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Selection<T> {
     bitmap: Bitmap,
     _marker: PhantomData<T>,
 }
 
+impl<T> Clone for Selection<T> {
+    fn clone(&self) -> Self {
+        Selection::from_bitmap(self.bitmap.clone())
+    }
+}
+
 impl<T> Selection<T> {
     pub fn empty() -> Self {
         Selection {
@@ -57,10 +94,9 @@ impl<T> Selection<T> {
     }
 
     pub fn filled(count: u32) -> Self {
-        Selection {
-            bitmap: (0..count).collect(),
-            _marker: PhantomData,
-        }
+        let mut bitmap = Bitmap::create();
+        bitmap.add_range(0..count as u64);
+        Selection::from_bitmap(bitmap)
     }
 
     pub fn of_row(row: Row<T>) -> Self {
@@ -84,6 +120,22 @@ impl<T> Selection<T> {
         self.bitmap.is_empty()
     }
 
+    pub fn contains(&self, row: Row<T>) -> bool {
+        self.bitmap.contains(row.as_u32())
+    }
+
+    pub fn intersection_len(&self, other: &Selection<T>) -> u64 {
+        self.bitmap.and_cardinality(&other.bitmap)
+    }
+
+    pub fn intersects(&self, other: &Selection<T>) -> bool {
+        self.bitmap.intersect(&other.bitmap)
+    }
+
+    pub fn is_subset(&self, other: &Selection<T>) -> bool {
+        self.bitmap.is_subset(&other.bitmap)
+    }
+
     pub fn rows(&self) -> impl Iterator<Item = Row<T>> + '_ {
         self.bitmap.iter().map(Row::from_u32)
     }
@@ -95,6 +147,34 @@ impl<T> Selection<T> {
     pub fn remove(&mut self, row: Row<T>) {
         self.bitmap.remove(row.as_u32());
     }
+
+    pub(crate) fn truncate(&mut self, len: usize) {
+        if let Some(max) = self.bitmap.maximum() {
+            if max as usize >= len {
+                self.bitmap.remove_range(len as u64..u64::from(max) + 1);
+            }
+        }
+    }
+
+    pub fn optimize(&mut self) {
+        self.bitmap.run_optimize();
+    }
+
+    pub fn pop_min(&mut self) -> Option<Row<T>> {
+        let value = self.bitmap.minimum()?;
+        self.bitmap.remove(value);
+        Some(Row::from_u32(value))
+    }
+
+    pub fn pop_max(&mut self) -> Option<Row<T>> {
+        let value = self.bitmap.maximum()?;
+        self.bitmap.remove(value);
+        Some(Row::from_u32(value))
+    }
+
+    pub fn drain_rows(&mut self) -> impl Iterator<Item = Row<T>> + '_ {
+        std::iter::from_fn(move || self.pop_min())
+    }
 }
 
 impl<T> Default for Selection<T> {
@@ -119,6 +199,14 @@ impl<T> BitOr for &Selection<T> {
     }
 }
 
+impl<T> Sub for &Selection<T> {
+    type Output = Selection<T>;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Selection::from_bitmap(self.bitmap.andnot(&rhs.bitmap))
+    }
+}
+
 impl<T> BitAndAssign<&Selection<T>> for Selection<T> {
     fn bitand_assign(&mut self, rhs: &Selection<T>) {
         self.bitmap.and_inplace(&rhs.bitmap);
@@ -131,6 +219,12 @@ impl<T> BitOrAssign<&Selection<T>> for Selection<T> {
     }
 }
 
+impl<T> SubAssign<&Selection<T>> for Selection<T> {
+    fn sub_assign(&mut self, rhs: &Selection<T>) {
+        self.bitmap.andnot_inplace(&rhs.bitmap);
+    }
+}
+
 impl<T> FromIterator<Row<T>> for Selection<T> {
     fn from_iter<I: IntoIterator<Item = Row<T>>>(iter: I) -> Self {
         Selection::from_bitmap(iter.into_iter().map(|row| row.as_u32()).collect())
@@ -1,5 +1,24 @@
+use std::borrow::{Borrow, Cow};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
 use std::iter::FromIterator;
+use std::mem;
+use std::ops::{Index, RangeBounds};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
+use crate::access::Accesses;
+use crate::aggregate::Groups;
+use crate::capped::Capped;
+use crate::format::{format_table, Columns};
+use crate::index::{
+    DiscreteIndex, FuzzyIndex, IndexStats, Lookup, PhoneticIndex, PrefixIndex, SortedIndex,
+    SuffixIndex, ValidityIndex, WindowIndex,
+};
+use crate::metrics::Metrics;
+use crate::sample::Reservoir;
 use crate::selection::{Row, Selection};
 
 pub trait Selectable: Clone {
@@ -10,6 +29,72 @@ pub trait Indexer<T> {
     fn new() -> Self;
     fn add(&mut self, row: Row<T>, item: &T);
     fn remove(&mut self, row: Row<T>, item: &T);
+
+    fn add_many(&mut self, items: &[(Row<T>, &T)]) {
+        for &(row, item) in items {
+            self.add(row, item);
+        }
+    }
+
+    fn remove_many(&mut self, items: &[(Row<T>, &T)]) {
+        for &(row, item) in items {
+            self.remove(row, item);
+        }
+    }
+
+    fn before_update(&mut self, row: Row<T>, item: &T) {
+        self.remove(row, item);
+    }
+
+    fn after_update(&mut self, row: Row<T>, item: &T) {
+        self.add(row, item);
+    }
+
+    fn before_update_many(&mut self, items: &[(Row<T>, &T)]) {
+        self.remove_many(items);
+    }
+
+    fn after_update_many(&mut self, items: &[(Row<T>, &T)]) {
+        self.add_many(items);
+    }
+
+    fn conflict(&self, _item: &T) -> Option<Row<T>> {
+        None
+    }
+
+    fn victim(&self) -> Option<Row<T>> {
+        None
+    }
+
+    fn stats(&self) -> Vec<(&'static str, IndexStats)> {
+        Vec::new()
+    }
+
+    fn advance(&mut self, _now: SystemTime) {}
+
+    fn warm(&mut self) {}
+}
+
+pub trait SharedSelectable {
+    type Indexer: Indexer<Arc<Self>>;
+}
+
+impl<T> Selectable for Arc<T>
+where
+    T: SharedSelectable,
+{
+    type Indexer = T::Indexer;
+}
+
+pub trait BoxedSelectable: Clone {
+    type Indexer: Indexer<Box<Self>>;
+}
+
+impl<T> Selectable for Box<T>
+where
+    T: BoxedSelectable,
+{
+    type Indexer = T::Indexer;
 }
 
 pub struct EmptyIndexer;
@@ -22,6 +107,10 @@ impl<T> Indexer<T> for EmptyIndexer {
     fn remove(&mut self, _: Row<T>, _: &T) {}
 }
 
+pub trait Event<T: Selectable> {
+    fn apply(self, table: &mut Table<T>);
+}
+
 #[derive(Clone, Debug)]
 pub struct Query<T, X>
 where
@@ -41,26 +130,42 @@ where
         &self.table.as_ref().indexer
     }
 
+    pub fn selection(&self) -> &Selection<T> {
+        &self.selection
+    }
+
     // selecting
 
     pub fn and(&mut self, selection: &Selection<T>) -> &mut Self {
         self.selection &= selection;
-        self
+        self.composed("and")
     }
 
     pub fn or(&mut self, selection: &Selection<T>) -> &mut Self {
         self.selection |= selection;
-        self
+        self.selection.truncate(self.table.as_ref().len());
+        self.composed("or")
+    }
+
+    pub fn and_not(&mut self, selection: &Selection<T>) -> &mut Self {
+        self.selection -= selection;
+        self.composed("and_not")
+    }
+
+    pub fn invert(&mut self) -> &mut Self {
+        self.selection = &Selection::filled(self.table.as_ref().len() as u32) - &self.selection;
+        self.composed("invert")
     }
 
     pub fn none(&mut self) -> &mut Self {
         self.selection = Selection::empty();
-        self
+        self.composed("none")
     }
 
     pub fn only_row(&mut self, row: Row<T>) -> &mut Self {
         self.selection = Selection::of_row(row);
-        self
+        self.selection.truncate(self.table.as_ref().len());
+        self.composed("only_row")
     }
 
     pub fn maybe_only_row(&mut self, maybe_row: Option<Row<T>>) -> &mut Self {
@@ -70,15 +175,124 @@ where
         }
     }
 
+    pub fn between<V, Q, R>(&mut self, index: &SortedIndex<T, V>, range: R) -> &mut Self
+    where
+        V: Ord + Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        self.and(&index.range(range))
+    }
+
+    pub fn starts_with(&mut self, index: &PrefixIndex<T>, prefix: &str) -> &mut Self {
+        self.and(&index.starts_with(prefix))
+    }
+
+    pub fn ends_with(&mut self, index: &SuffixIndex<T>, suffix: &str) -> &mut Self {
+        self.and(&index.ends_with(suffix))
+    }
+
+    pub fn matches_within(
+        &mut self,
+        index: &FuzzyIndex<T>,
+        value: &str,
+        max_distance: usize,
+    ) -> &mut Self {
+        self.and(&index.matches_within(value, max_distance))
+    }
+
+    pub fn sounds_like(&mut self, index: &PhoneticIndex<T>, value: &str) -> &mut Self {
+        self.and(index.sounds_like(value))
+    }
+
+    pub fn in_window(
+        &mut self,
+        index: &WindowIndex<T>,
+        now: SystemTime,
+        length: Duration,
+    ) -> &mut Self {
+        self.and(&index.in_window(now, length))
+    }
+
+    pub fn in_current_window(&mut self, index: &WindowIndex<T>) -> &mut Self {
+        self.and(index.current())
+    }
+
+    pub fn valid_at<V: Ord>(&mut self, index: &ValidityIndex<T, V>, at: &V) -> &mut Self {
+        let valid = index.valid_at(at);
+        self.and(&valid)
+    }
+
+    pub fn where_in<U, Y, K, I, F>(&mut self, index: &I, other: &Query<U, Y>, key: F) -> &mut Self
+    where
+        U: Selectable,
+        Y: AsRef<Table<U>>,
+        I: Lookup<T, K>,
+        F: Fn(&U) -> K,
+    {
+        self.and(&other.related(index, key))
+    }
+
+    pub fn where_not_in<U, Y, K, I, F>(
+        &mut self,
+        index: &I,
+        other: &Query<U, Y>,
+        key: F,
+    ) -> &mut Self
+    where
+        U: Selectable,
+        Y: AsRef<Table<U>>,
+        I: Lookup<T, K>,
+        F: Fn(&U) -> K,
+    {
+        self.and_not(&other.related(index, key))
+    }
+
     pub fn only(&mut self, selection: Selection<T>) -> &mut Self {
         self.selection = selection;
+        self.selection.truncate(self.table.as_ref().len());
+        self.composed("only")
+    }
+
+    fn composed(&mut self, _operation: &'static str) -> &mut Self {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            operation = _operation,
+            rows = self.selection.len(),
+            "query composed"
+        );
         self
     }
 
     // operations on the selected items
 
     pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
-        self.table.as_ref().retrieve_many(self.selection.rows())
+        self.observed(self.table.as_ref().retrieve_many(self.selection.rows()))
+    }
+
+    pub fn pluck<'query, V, F>(&'query self, field: F) -> impl Iterator<Item = V> + 'query
+    where
+        V: Clone,
+        F: Fn(&T) -> &V + 'query,
+    {
+        let table = self.table.as_ref();
+        self.observed(
+            self.selection
+                .rows()
+                .map(move |row| field(table.read_row(row)).clone()),
+        )
+    }
+
+    pub fn project<'query, V, F>(&'query self, fields: F) -> impl Iterator<Item = V> + 'query
+    where
+        F: Fn(&T) -> V + 'query,
+    {
+        let table = self.table.as_ref();
+        self.observed(
+            self.selection
+                .rows()
+                .map(move |row| fields(table.read_row(row))),
+        )
     }
 
     pub fn first(&self) -> Option<T> {
@@ -92,9 +306,180 @@ where
         self.iter().collect()
     }
 
+    pub fn estimated_rows(&self) -> u64 {
+        self.selection.len()
+    }
+
+    pub fn estimated_cost(&self) -> u64 {
+        self.estimated_rows() * mem::size_of::<T>() as u64
+    }
+
+    pub fn overlaps<Y: AsRef<Table<T>>>(&self, other: &Query<T, Y>) -> bool {
+        self.selection.intersects(&other.selection)
+    }
+
+    pub fn is_disjoint_with<Y: AsRef<Table<T>>>(&self, other: &Query<T, Y>) -> bool {
+        !self.overlaps(other)
+    }
+
+    pub fn is_subset_of<Y: AsRef<Table<T>>>(&self, other: &Query<T, Y>) -> bool {
+        self.selection.is_subset(&other.selection)
+    }
+
+    pub fn collect_within(&self, budget: Budget) -> Result<Vec<T>, Truncated<T>> {
+        let started = Instant::now();
+        let mut items = Vec::new();
+        let mut exceeded = None;
+        for row in self.selection.rows() {
+            if budget.max_rows.is_some_and(|max| items.len() as u64 >= max) {
+                exceeded = Some(Exceeded::Rows);
+            } else if budget
+                .max_duration
+                .is_some_and(|max| started.elapsed() >= max)
+            {
+                exceeded = Some(Exceeded::Duration);
+            }
+            if exceeded.is_some() {
+                break;
+            }
+            items.push(self.table.as_ref().retrieve_row(row));
+        }
+        self.report(items.len() as u64, Some(started));
+        match exceeded {
+            Some(reason) => Err(Truncated { items, reason }),
+            None => Ok(items),
+        }
+    }
+
     pub fn count(&self) -> u64 {
         self.selection.len()
     }
+
+    pub fn page_after<V: Ord>(
+        &self,
+        index: &SortedIndex<T, V>,
+        after: Option<(&V, Row<T>)>,
+        limit: usize,
+    ) -> Vec<Row<T>> {
+        if self.selection.len() >= index.key_count() as u64 {
+            return index.page_within(&self.selection, after, limit);
+        }
+
+        let table = self.table.as_ref();
+        let mut rows: Vec<_> = self
+            .selection
+            .rows()
+            .map(|row| (index.key(table.borrow_row(row)), row))
+            .filter(|&(key, row)| after.is_none_or(|after| (key, row) > after))
+            .collect();
+        if rows.len() > limit {
+            if limit > 0 {
+                rows.select_nth_unstable(limit - 1);
+            }
+            rows.truncate(limit);
+        }
+        rows.sort_unstable();
+        rows.into_iter().map(|(_, row)| row).collect()
+    }
+
+    pub fn facet<'index, V, S>(
+        &self,
+        index: &'index DiscreteIndex<T, V, S>,
+    ) -> HashMap<&'index V, u64>
+    where
+        V: Eq + Hash,
+        S: BuildHasher,
+    {
+        index.facet(&self.selection)
+    }
+
+    pub fn group_by<'query, V, S>(
+        &'query self,
+        index: &'query DiscreteIndex<T, V, S>,
+    ) -> Groups<'query, T, V, S>
+    where
+        V: Eq + Hash + Clone,
+        S: BuildHasher,
+    {
+        Groups::new(self.table.as_ref(), &self.selection, index)
+    }
+
+    pub fn stream(&self, batch_size: usize) -> Stream<T> {
+        Stream {
+            remaining: self.selection.clone(),
+            batch_size,
+            generation: self.table.as_ref().generation,
+            skipped: 0,
+        }
+    }
+
+    fn related<U, K, I, F>(&self, index: &I, key: F) -> Selection<U>
+    where
+        I: Lookup<U, K>,
+        F: Fn(&T) -> K,
+    {
+        let table = self.table.as_ref();
+        let mut related = Selection::empty();
+        for row in self.selection.rows() {
+            related |= &index.lookup(&key(table.borrow_row(row)));
+        }
+        related
+    }
+
+    fn observed<I: Iterator>(&self, items: I) -> Observed<'_, T, X, I> {
+        Observed {
+            items,
+            query: self,
+            rows: 0,
+            started: self.table.as_ref().metrics.as_ref().map(|_| Instant::now()),
+        }
+    }
+
+    fn report(&self, rows: u64, started: Option<Instant>) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(rows, "query materialized");
+        self.table.as_ref().report_query(rows, started);
+    }
+}
+
+impl<T, X> Query<T, X>
+where
+    T: Selectable + Columns,
+    X: AsRef<Table<T>>,
+{
+    pub fn format_table(&self) -> String {
+        format_table(self.iter())
+    }
+}
+
+impl<'table, T> Query<T, &'table Table<T>>
+where
+    T: Selectable,
+{
+    pub fn iter_ref(&self) -> impl Iterator<Item = &'table T> + '_ {
+        let table = self.table;
+        self.observed(self.selection.rows().map(move |row| table.read_row(row)))
+    }
+
+    pub fn first_ref(&self) -> Option<&'table T> {
+        self.iter_ref().next()
+    }
+
+    pub fn iter_cow(&self) -> impl Iterator<Item = Cow<'table, T>> + '_ {
+        self.iter_ref().map(Cow::Borrowed)
+    }
+
+    pub fn first_cow(&self) -> Option<Cow<'table, T>> {
+        self.iter_cow().next()
+    }
+
+    pub fn pluck_ref<V, F>(&self, field: F) -> impl Iterator<Item = &'table V> + '_
+    where
+        V: ?Sized + 'table,
+        F: Fn(&'table T) -> &'table V + 'table,
+    {
+        self.iter_ref().map(field)
+    }
 }
 
 impl<T, X> Query<T, X>
@@ -103,19 +488,319 @@ where
     X: AsRef<Table<T>> + AsMut<Table<T>>,
 {
     pub fn apply<F: Fn(&mut T) + Clone>(&mut self, update: F) {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("apply", rows = self.selection.len()).entered();
+        let started = self.table.as_ref().metrics.as_ref().map(|_| Instant::now());
         for row in self.selection.rows() {
             self.table.as_mut().update_row(row, update.clone())
         }
+        self.report_update(self.selection.len(), started);
+    }
+
+    pub fn apply_or_insert<F, D>(&mut self, update: F, default: D)
+    where
+        F: Fn(&mut T) + Clone,
+        D: FnOnce() -> T,
+    {
+        if self.selection.is_empty() {
+            let row = self.table.as_mut().insert(default());
+            self.selection = Selection::of_row(row);
+        } else {
+            self.apply(update);
+        }
+    }
+
+    pub fn apply_atomic<F: Fn(&mut T)>(&mut self, update: F) {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("apply_atomic", rows = self.selection.len()).entered();
+        let started = self.table.as_ref().metrics.as_ref().map(|_| Instant::now());
+        let table = self.table.as_ref();
+        let updated: Vec<_> = self
+            .selection
+            .rows()
+            .map(|row| {
+                let mut item = table.borrow_row(row).clone();
+                update(&mut item);
+                (row, item)
+            })
+            .collect();
+        let table = self.table.as_mut();
+        for (row, item) in updated {
+            table.replace_row(row, item);
+        }
+        self.report_update(self.selection.len(), started);
+    }
+
+    pub fn apply_batched<F: Fn(&mut T)>(&mut self, update: F) {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("apply_batched", rows = self.selection.len()).entered();
+        let started = self.table.as_ref().metrics.as_ref().map(|_| Instant::now());
+        let rows: Vec<_> = self.selection.rows().collect();
+        self.table.as_mut().update_rows(&rows, update);
+        self.report_update(self.selection.len(), started);
+    }
+
+    fn report_update(&self, rows: u64, started: Option<Instant>) {
+        if let (Some(metrics), Some(started)) = (&self.table.as_ref().metrics, started) {
+            metrics.updated(rows, started.elapsed());
+        }
+    }
+}
+
+impl<T, X> Query<T, X>
+where
+    T: Selectable + PartialEq,
+    X: AsRef<Table<T>> + AsMut<Table<T>>,
+{
+    pub fn apply_if<P, F>(&mut self, predicate: P, update: F) -> Applied
+    where
+        P: Fn(&T) -> bool,
+        F: Fn(&mut T),
+    {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("apply_if", rows = self.selection.len()).entered();
+        let started = self.table.as_ref().metrics.as_ref().map(|_| Instant::now());
+        let mut applied = Applied::default();
+        let table = self.table.as_mut();
+        for row in self.selection.rows() {
+            let item = table.borrow_row(row);
+            if !predicate(item) {
+                continue;
+            }
+            applied.matched += 1;
+            let mut updated = item.clone();
+            update(&mut updated);
+            if updated != *item {
+                applied.changed += 1;
+                table.replace_row(row, updated);
+            }
+        }
+        self.report_update(applied.matched, started);
+        applied
+    }
+}
+
+impl<T, X> Query<Arc<T>, X>
+where
+    T: SharedSelectable + Clone,
+    X: AsRef<Table<Arc<T>>> + AsMut<Table<Arc<T>>>,
+{
+    pub fn apply_shared<F: Fn(&mut T) + Clone>(&mut self, update: F) {
+        self.apply(move |item| update(Arc::make_mut(item)))
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Observed<'query, T, X, I>
+where
+    T: Selectable,
+    X: AsRef<Table<T>>,
+{
+    items: I,
+    query: &'query Query<T, X>,
+    rows: u64,
+    started: Option<Instant>,
+}
+
+impl<'query, T, X, I> Iterator for Observed<'query, T, X, I>
+where
+    T: Selectable,
+    X: AsRef<Table<T>>,
+    I: Iterator,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.items.next()?;
+        self.rows += 1;
+        Some(item)
+    }
+}
+
+impl<'query, T, X, I> Drop for Observed<'query, T, X, I>
+where
+    T: Selectable,
+    X: AsRef<Table<T>>,
+{
+    fn drop(&mut self) {
+        self.query.report(self.rows, self.started);
+    }
+}
+
+pub struct Stream<T> {
+    remaining: Selection<T>,
+    batch_size: usize,
+    generation: u64,
+    skipped: u64,
+}
+
+impl<T> Stream<T>
+where
+    T: Selectable,
+{
+    pub fn next_batch(&mut self, table: &Table<T>) -> Option<Vec<T>> {
+        let started = table.metrics.as_ref().map(|_| Instant::now());
+        if table.renumbered > self.generation {
+            self.skipped += self.remaining.len();
+            self.remaining = Selection::empty();
+        }
+        if self.remaining.is_empty() {
+            return None;
+        }
+
+        let rows: Vec<_> = self.remaining.rows().take(self.batch_size.max(1)).collect();
+        let mut batch = Vec::with_capacity(rows.len());
+        for row in rows {
+            self.remaining.remove(row);
+            let replaced = table
+                .capped
+                .as_ref()
+                .is_some_and(|capped| capped.replaced_since(row, self.generation));
+            match table.items.get(row.as_index()) {
+                Some(item) if !replaced => batch.push(item.clone()),
+                _ => self.skipped += 1,
+            }
+        }
+        table.report_query(batch.len() as u64, started);
+        Some(batch)
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.remaining.len()
+    }
+
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+}
+
+pub struct Entry<'table, T>
+where
+    T: Selectable,
+{
+    table: &'table mut Table<T>,
+    row: Option<Row<T>>,
+}
+
+impl<'table, T> Entry<'table, T>
+where
+    T: Selectable,
+{
+    pub fn row(&self) -> Option<Row<T>> {
+        self.row
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        self.table.items.get(self.row?.as_index())
+    }
+
+    pub fn or_insert(self, item: T) -> Self {
+        self.or_insert_with(|| item)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> T>(mut self, default: F) -> Self {
+        if self.row.is_none() {
+            self.row = Some(self.table.insert(default()));
+        }
+        self
+    }
+
+    pub fn and_modify<F: FnOnce(&mut T)>(self, update: F) -> Self {
+        if let Some(row) = self.row {
+            self.table.update_row(row, update);
+        }
+        self
     }
 }
 
 #[derive(Debug)]
+pub struct Conflict<T> {
+    pub row: Row<T>,
+    pub item: T,
+}
+
+impl<T> fmt::Display for Conflict<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "item conflicts with existing row {}",
+            self.row.as_index()
+        )
+    }
+}
+
+impl<T> Error for Conflict<T> where T: fmt::Debug {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccessStats {
+    pub reads: u64,
+    pub last_read: Option<SystemTime>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Eviction {
+    LeastRecentlyUsed,
+    Oldest,
+    LowestPriority,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Budget {
+    pub max_rows: Option<u64>,
+    pub max_duration: Option<Duration>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exceeded {
+    Rows,
+    Duration,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Truncated<T> {
+    pub items: Vec<T>,
+    pub reason: Exceeded,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Applied {
+    pub matched: u64,
+    pub changed: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TableStats {
+    pub rows: usize,
+    pub capacity: usize,
+    pub item_bytes: usize,
+    pub indexes: Vec<(&'static str, IndexStats)>,
+}
+
 pub struct Table<T>
 where
     T: Selectable,
 {
     items: Vec<T>,
     indexer: T::Indexer,
+    metrics: Option<Arc<dyn Metrics + Send + Sync>>,
+    sample: Option<Reservoir<T>>,
+    capped: Option<Capped>,
+    accesses: Option<Accesses>,
+    generation: u64,
+    renumbered: u64,
+}
+
+impl<T> fmt::Debug for Table<T>
+where
+    T: Selectable + fmt::Debug,
+    T::Indexer: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Table")
+            .field("items", &self.items)
+            .field("indexer", &self.indexer)
+            .finish()
+    }
 }
 
 impl<T> AsRef<Table<T>> for Table<T>
@@ -136,6 +821,97 @@ where
     }
 }
 
+impl<T> IntoIterator for Table<T>
+where
+    T: Selectable,
+{
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+impl<'table, T> IntoIterator for &'table Table<T>
+where
+    T: Selectable,
+{
+    type Item = &'table T;
+    type IntoIter = std::slice::Iter<'table, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl<T> Index<Row<T>> for Table<T>
+where
+    T: Selectable,
+{
+    type Output = T;
+
+    fn index(&self, row: Row<T>) -> &T {
+        match self.items.get(row.as_index()) {
+            Some(item) => item,
+            None => panic!(
+                "row {} is out of bounds for a table of {} items",
+                row.as_index(),
+                self.len()
+            ),
+        }
+    }
+}
+
+impl<T> Clone for Table<T>
+where
+    T: Selectable,
+{
+    fn clone(&self) -> Self {
+        let mut table: Self = self.items.iter().cloned().collect();
+        table.metrics = self.metrics.clone();
+        table.sample = self.sample.clone();
+        table.capped = self.capped.clone();
+        table.accesses = self.accesses.clone();
+        table.generation = self.generation;
+        table.renumbered = self.renumbered;
+        table
+    }
+}
+
+impl<T> PartialEq for Table<T>
+where
+    T: Selectable + PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.items == other.items
+    }
+}
+
+impl<T> Eq for Table<T> where T: Selectable + Eq {}
+
+impl<T> FromIterator<T> for Table<T>
+where
+    T: Selectable,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut table = Table::in_memory();
+        table.extend(iter);
+        table
+    }
+}
+
+impl<T> Extend<T> for Table<T>
+where
+    T: Selectable,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.insert(item);
+        }
+    }
+}
+
 impl<T> Table<T>
 where
     T: Selectable,
@@ -144,7 +920,41 @@ where
         Table {
             items: Vec::new(),
             indexer: T::Indexer::new(),
+            metrics: None,
+            sample: None,
+            capped: None,
+            accesses: None,
+            generation: 0,
+            renumbered: 0,
+        }
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<dyn Metrics + Send + Sync>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn with_sample(mut self, size: usize) -> Self {
+        let mut sample = Reservoir::new(size);
+        for index in 0..self.items.len() {
+            sample.offer(unsafe { Row::from_index(index) });
         }
+        self.sample = Some(sample);
+        self
+    }
+
+    pub fn with_eviction(mut self, max_rows: usize, eviction: Eviction) -> Self {
+        self.capped = Some(Capped::new(max_rows, eviction, self.items.len()));
+        self
+    }
+
+    pub fn with_access_stats(mut self) -> Self {
+        self.accesses = Some(Accesses::new(self.items.len()));
+        self
+    }
+
+    pub fn indexer(&self) -> &T::Indexer {
+        &self.indexer
     }
 
     pub fn select(&self) -> Query<T, &Table<T>> {
@@ -154,10 +964,88 @@ where
         }
     }
 
-    pub fn insert(&mut self, item: T) {
-        let row = unsafe { Row::from_index(self.items.len()) };
-        self.indexer.add(row, &item);
-        self.items.push(item);
+    pub fn insert(&mut self, item: T) -> Row<T> {
+        let started = self.metrics.as_ref().map(|_| Instant::now());
+        let victim = match &mut self.capped {
+            Some(capped) => capped.victim(&self.indexer),
+            None => None,
+        };
+        let row = match victim {
+            Some(row) => {
+                self.overwrite_row(row, item);
+                self.generation += 1;
+                if let Some(capped) = &mut self.capped {
+                    capped.touch(row);
+                    capped.replaced(row, self.generation);
+                }
+                if let Some(accesses) = &self.accesses {
+                    accesses.replaced(row);
+                }
+                row
+            }
+            None => {
+                let row = unsafe { Row::from_index(self.items.len()) };
+                self.indexer.add(row, &item);
+                self.items.push(item);
+                if let Some(sample) = &mut self.sample {
+                    sample.offer(row);
+                }
+                if let Some(capped) = &mut self.capped {
+                    capped.inserted();
+                }
+                if let Some(accesses) = &mut self.accesses {
+                    accesses.inserted();
+                }
+                row
+            }
+        };
+        if let (Some(metrics), Some(started)) = (&self.metrics, started) {
+            metrics.inserted(started.elapsed());
+        }
+        row
+    }
+
+    pub fn replay<E, I>(&mut self, events: I)
+    where
+        E: Event<T>,
+        I: IntoIterator<Item = E>,
+    {
+        for event in events {
+            self.apply_event(event);
+        }
+    }
+
+    pub fn apply_event<E: Event<T>>(&mut self, event: E) {
+        event.apply(self);
+    }
+
+    pub fn insert_unique(&mut self, item: T) -> Result<Row<T>, Conflict<T>> {
+        match self.indexer.conflict(&item) {
+            Some(row) => Err(Conflict { row, item }),
+            None => Ok(self.insert(item)),
+        }
+    }
+
+    pub fn insert_or_get(&mut self, item: T) -> Row<T> {
+        self.insert_unique(item)
+            .unwrap_or_else(|conflict| conflict.row)
+    }
+
+    pub fn entry(&mut self, row: Option<Row<T>>) -> Entry<'_, T> {
+        // a row from before a split_off can point past the end, so it counts as vacant
+        let row = row.filter(|row| row.as_index() < self.items.len());
+        Entry { table: self, row }
+    }
+
+    pub fn sample_view(&self) -> Option<Query<T, &Table<T>>> {
+        let sample = self.sample.as_ref()?;
+        let mut query = self.select();
+        query.only(sample.selection());
+        Some(query)
+    }
+
+    pub fn access_stats(&self) -> Option<Vec<(Row<T>, AccessStats)>> {
+        Some(self.accesses.as_ref()?.stats())
     }
 
     pub fn update(&mut self) -> Query<T, &mut Table<T>> {
@@ -167,6 +1055,26 @@ where
         }
     }
 
+    // kept rows are renumbered, so rows and selections taken before the split no longer
+    // name the same items; entry and query inputs are clipped to the table so stale
+    // rows can never read past the end, but callers must look rows up again
+    pub fn split_off(&mut self, selection: &Selection<T>) -> Table<T> {
+        let (taken, kept): (Vec<_>, Vec<_>) = mem::take(&mut self.items)
+            .into_iter()
+            .enumerate()
+            .partition(|(index, _)| selection.contains(unsafe { Row::from_index(*index) }));
+        let taken = self.rebuilt(taken);
+        let generation = self.generation + 1;
+        *self = self.rebuilt(kept);
+        self.generation = generation;
+        self.renumbered = generation;
+        taken
+    }
+
+    pub fn absorb(&mut self, other: Table<T>) {
+        self.extend(other.items);
+    }
+
     pub fn len(&self) -> usize {
         self.items.len()
     }
@@ -175,15 +1083,112 @@ where
         self.items.is_empty()
     }
 
-    fn update_row<F: Fn(&mut T)>(&mut self, row: Row<T>, update: F) {
-        let mut item = unsafe { self.items.get_unchecked_mut(row.as_index()) };
-        self.indexer.remove(row, &item);
-        update(&mut item);
-        self.indexer.add(row, &item);
+    pub fn advance_window(&mut self, now: SystemTime) {
+        self.indexer.advance(now);
+    }
+
+    pub fn warm(&mut self) {
+        self.indexer.warm();
+    }
+
+    pub fn duplicates_by<K, F>(&self, key: F) -> Vec<Selection<T>>
+    where
+        K: Eq + Hash,
+        F: Fn(&T) -> K,
+    {
+        let mut groups: HashMap<K, Selection<T>> = HashMap::new();
+        for (index, item) in self.items.iter().enumerate() {
+            let row = unsafe { Row::from_index(index) };
+            groups.entry(key(item)).or_default().add(row);
+        }
+
+        let mut duplicates: Vec<_> = groups
+            .into_values()
+            .filter(|selection| selection.len() > 1)
+            .collect();
+        duplicates.sort_by_key(|selection| selection.rows().next());
+        duplicates
+    }
+
+    pub fn stats(&self) -> TableStats {
+        TableStats {
+            rows: self.items.len(),
+            capacity: self.items.capacity(),
+            item_bytes: self.items.capacity() * std::mem::size_of::<T>(),
+            indexes: self.indexer.stats(),
+        }
+    }
+
+    fn update_row<F: FnOnce(&mut T)>(&mut self, row: Row<T>, update: F) {
+        let item = unsafe { self.items.get_unchecked_mut(row.as_index()) };
+        self.indexer.before_update(row, item);
+        update(item);
+        self.indexer.after_update(row, item);
+    }
+
+    fn rebuilt(&self, rows: Vec<(usize, T)>) -> Self {
+        let (indexes, items): (Vec<_>, Vec<_>) = rows.into_iter().unzip();
+        let mut table = Table::in_memory();
+        table.sample = self.sample.as_ref().map(Reservoir::emptied);
+        table.extend(items);
+        table.accesses = self
+            .accesses
+            .as_ref()
+            .map(|accesses| accesses.retained(&indexes));
+        table.capped = self.capped.as_ref().map(|capped| capped.retained(&indexes));
+        table.metrics = self.metrics.clone();
+        table
+    }
+
+    fn update_rows<F: Fn(&mut T)>(&mut self, rows: &[Row<T>], update: F) {
+        let Table { items, indexer, .. } = self;
+        indexer.before_update_many(&batch_of(items, rows));
+        for &row in rows {
+            update(unsafe { items.get_unchecked_mut(row.as_index()) });
+        }
+        indexer.after_update_many(&batch_of(items, rows));
+    }
+
+    fn replace_row(&mut self, row: Row<T>, item: T) {
+        let existing = unsafe { self.items.get_unchecked_mut(row.as_index()) };
+        self.indexer.before_update(row, existing);
+        *existing = item;
+        self.indexer.after_update(row, existing);
+    }
+
+    fn overwrite_row(&mut self, row: Row<T>, item: T) {
+        let existing = unsafe { self.items.get_unchecked_mut(row.as_index()) };
+        let old = mem::replace(existing, item);
+        self.indexer.remove(row, &old);
+        self.indexer.add(row, existing);
+    }
+
+    fn report_query(&self, rows: u64, started: Option<Instant>) {
+        if let (Some(metrics), Some(started)) = (&self.metrics, started) {
+            metrics.queried(rows, started.elapsed());
+        }
+    }
+
+    pub(crate) fn borrow_row(&self, row: Row<T>) -> &T {
+        unsafe { self.items.get_unchecked(row.as_index()) }
+    }
+
+    fn read_row(&self, row: Row<T>) -> &T {
+        self.touch(row);
+        self.borrow_row(row)
     }
 
     fn retrieve_row(&self, row: Row<T>) -> T {
-        unsafe { self.items.get_unchecked(row.as_index()).clone() }
+        self.read_row(row).clone()
+    }
+
+    fn touch(&self, row: Row<T>) {
+        if let Some(capped) = &self.capped {
+            capped.touch(row);
+        }
+        if let Some(accesses) = &self.accesses {
+            accesses.read(row);
+        }
     }
 
     fn retrieve_many<'table, I>(&'table self, rows: I) -> impl Iterator<Item = T> + 'table
@@ -194,3 +1199,9 @@ where
         rows.into_iter().map(move |row| self.retrieve_row(row))
     }
 }
+
+fn batch_of<'table, T>(items: &'table [T], rows: &[Row<T>]) -> Vec<(Row<T>, &'table T)> {
+    rows.iter()
+        .map(|&row| (row, unsafe { items.get_unchecked(row.as_index()) }))
+        .collect()
+}
@@ -1,6 +1,28 @@
-use crate::index::{BooleanIndex, DiscreteIndex, Index, UniqueIndex};
-use crate::selection::Row;
-use crate::table::{EmptyIndexer, Indexer, Query, Selectable, Table};
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::BuildHasherDefault;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::aggregate::{count, max, min, sum};
+use crate::format::Columns;
+use crate::index::{
+    join_selections, AdaptiveIndex, AggregateIndex, BooleanIndex, ComparatorIndex,
+    DenseDiscreteIndex, DenseIndex, DiscreteIndex, EdgeIndex, ExpressionIndex, FuzzyIndex, Index,
+    IndexStats, InsertionOrderIndex, MinMaxIndex, PhoneticIndex, PrefixIndex, PriorityIndex,
+    QuantileIndex, SortedIndex, SuffixIndex, TreeIndex, UniqueIndex, ValidityIndex, WindowIndex,
+};
+use crate::intern::Interner;
+use crate::metrics::{Metrics, SlowQueries};
+use crate::partition::PartitionedTable;
+use crate::selection::{Row, Selection};
+use crate::table::{
+    Applied, BoxedSelectable, Budget, EmptyIndexer, Entry, Event, Eviction, Exceeded, Indexer,
+    Query, Selectable, SharedSelectable, Table,
+};
 
 #[derive(Clone)]
 struct UnindexedThing {
@@ -11,7 +33,7 @@ impl Selectable for UnindexedThing {
     type Indexer = EmptyIndexer;
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 struct Person {
     id: u32,
     first_name: String,
@@ -23,10 +45,35 @@ impl Selectable for Person {
     type Indexer = PersonIndexer;
 }
 
+impl Columns for Person {
+    fn headers() -> Vec<&'static str> {
+        vec!["id", "first_name", "last_name", "age"]
+    }
+
+    fn columns(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.first_name.clone(),
+            self.last_name.clone(),
+            self.age.to_string(),
+        ]
+    }
+}
+
 struct PersonIndexer {
     by_id: UniqueIndex<Person, u32>,
     by_last_name: DiscreteIndex<Person, String>,
+    by_age: SortedIndex<Person, u8>,
+    by_first_name_prefix: PrefixIndex<Person>,
+    by_last_name_suffix: SuffixIndex<Person>,
+    by_last_name_fuzzy: FuzzyIndex<Person>,
+    by_last_name_sound: PhoneticIndex<Person>,
+    age_by_last_name: AggregateIndex<Person, u32, String>,
+    age_range: MinMaxIndex<Person, u8>,
+    age_quantiles: QuantileIndex<Person, u8>,
+    seniority: PriorityIndex<Person, u8>,
     adults: BooleanIndex<Person>,
+    by_full_name: ExpressionIndex<Person, String>,
 }
 
 impl Indexer<Person> for PersonIndexer {
@@ -34,7 +81,22 @@ impl Indexer<Person> for PersonIndexer {
         Self {
             by_id: UniqueIndex::new(|person| person.id),
             by_last_name: DiscreteIndex::new(|person| &person.last_name),
+            by_age: SortedIndex::new(|person| &person.age),
+            by_first_name_prefix: PrefixIndex::new(|person| &person.first_name),
+            by_last_name_suffix: SuffixIndex::new(|person| &person.last_name),
+            by_last_name_fuzzy: FuzzyIndex::new(|person| &person.last_name),
+            by_last_name_sound: PhoneticIndex::new(|person| &person.last_name),
+            age_by_last_name: AggregateIndex::grouped(
+                |person| person.last_name.clone(),
+                |person| person.age as u32,
+            ),
+            age_range: MinMaxIndex::new(|person| person.age),
+            age_quantiles: QuantileIndex::new(|person| person.age),
+            seniority: PriorityIndex::new(|person| person.age),
             adults: BooleanIndex::new(|person| person.age >= 18),
+            by_full_name: ExpressionIndex::new(|person| {
+                format!("{} {}", person.first_name, person.last_name)
+            }),
         }
     }
 
@@ -42,14 +104,131 @@ impl Indexer<Person> for PersonIndexer {
     fn add(&mut self, row: Row<Person>, item: &Person) {
         self.by_id.add(row, item);
         self.by_last_name.add(row, item);
+        self.by_age.add(row, item);
+        self.by_first_name_prefix.add(row, item);
+        self.by_last_name_suffix.add(row, item);
+        self.by_last_name_fuzzy.add(row, item);
+        self.by_last_name_sound.add(row, item);
+        self.age_by_last_name.add(row, item);
+        self.age_range.add(row, item);
+        self.age_quantiles.add(row, item);
+        self.seniority.add(row, item);
         self.adults.add(row, item);
+        self.by_full_name.add(row, item);
     }
 
     // TODO: this can be derivable, or implemented through reflection?
     fn remove(&mut self, row: Row<Person>, item: &Person) {
         self.by_id.remove(row, item);
         self.by_last_name.remove(row, item);
+        self.by_age.remove(row, item);
+        self.by_first_name_prefix.remove(row, item);
+        self.by_last_name_suffix.remove(row, item);
+        self.by_last_name_fuzzy.remove(row, item);
+        self.by_last_name_sound.remove(row, item);
+        self.age_by_last_name.remove(row, item);
+        self.age_range.remove(row, item);
+        self.age_quantiles.remove(row, item);
+        self.seniority.remove(row, item);
         self.adults.remove(row, item);
+        self.by_full_name.remove(row, item);
+    }
+
+    fn before_update(&mut self, row: Row<Person>, item: &Person) {
+        self.by_id.before_update(row, item);
+        self.by_last_name.before_update(row, item);
+        self.by_age.before_update(row, item);
+        self.by_first_name_prefix.before_update(row, item);
+        self.by_last_name_suffix.before_update(row, item);
+        self.by_last_name_fuzzy.before_update(row, item);
+        self.by_last_name_sound.before_update(row, item);
+        self.age_by_last_name.before_update(row, item);
+        self.age_range.before_update(row, item);
+        self.age_quantiles.before_update(row, item);
+        self.seniority.before_update(row, item);
+        self.adults.before_update(row, item);
+        self.by_full_name.before_update(row, item);
+    }
+
+    fn after_update(&mut self, row: Row<Person>, item: &Person) {
+        self.by_id.after_update(row, item);
+        self.by_last_name.after_update(row, item);
+        self.by_age.after_update(row, item);
+        self.by_first_name_prefix.after_update(row, item);
+        self.by_last_name_suffix.after_update(row, item);
+        self.by_last_name_fuzzy.after_update(row, item);
+        self.by_last_name_sound.after_update(row, item);
+        self.age_by_last_name.after_update(row, item);
+        self.age_range.after_update(row, item);
+        self.age_quantiles.after_update(row, item);
+        self.seniority.after_update(row, item);
+        self.adults.after_update(row, item);
+        self.by_full_name.after_update(row, item);
+    }
+
+    fn before_update_many(&mut self, items: &[(Row<Person>, &Person)]) {
+        self.by_id.before_update_many(items);
+        self.by_last_name.before_update_many(items);
+        self.by_age.before_update_many(items);
+        self.by_first_name_prefix.before_update_many(items);
+        self.by_last_name_suffix.before_update_many(items);
+        self.by_last_name_fuzzy.before_update_many(items);
+        self.by_last_name_sound.before_update_many(items);
+        self.age_by_last_name.before_update_many(items);
+        self.age_range.before_update_many(items);
+        self.age_quantiles.before_update_many(items);
+        self.seniority.before_update_many(items);
+        self.adults.before_update_many(items);
+        self.by_full_name.before_update_many(items);
+    }
+
+    fn after_update_many(&mut self, items: &[(Row<Person>, &Person)]) {
+        self.by_id.after_update_many(items);
+        self.by_last_name.after_update_many(items);
+        self.by_age.after_update_many(items);
+        self.by_first_name_prefix.after_update_many(items);
+        self.by_last_name_suffix.after_update_many(items);
+        self.by_last_name_fuzzy.after_update_many(items);
+        self.by_last_name_sound.after_update_many(items);
+        self.age_by_last_name.after_update_many(items);
+        self.age_range.after_update_many(items);
+        self.age_quantiles.after_update_many(items);
+        self.seniority.after_update_many(items);
+        self.adults.after_update_many(items);
+        self.by_full_name.after_update_many(items);
+    }
+
+    fn warm(&mut self) {
+        self.by_last_name.warm();
+        self.by_age.warm();
+        self.adults.warm();
+        self.by_full_name.warm();
+    }
+
+    fn conflict(&self, item: &Person) -> Option<Row<Person>> {
+        self.by_id.conflict(item)
+    }
+
+    fn victim(&self) -> Option<Row<Person>> {
+        self.seniority.lowest()
+    }
+
+    fn stats(&self) -> Vec<(&'static str, IndexStats)> {
+        vec![
+            ("by_id", self.by_id.stats()),
+            ("by_last_name", self.by_last_name.stats()),
+            ("by_age", self.by_age.stats()),
+            ("by_first_name_prefix", self.by_first_name_prefix.stats()),
+            ("by_last_name_suffix", self.by_last_name_suffix.stats()),
+            ("by_last_name_fuzzy", self.by_last_name_fuzzy.stats()),
+            ("by_last_name_sound", self.by_last_name_sound.stats()),
+            ("age_by_last_name", self.age_by_last_name.stats()),
+            ("age_range", self.age_range.stats()),
+            ("age_quantiles", self.age_quantiles.stats()),
+            ("seniority", self.seniority.stats()),
+            ("adults", self.adults.stats()),
+            ("by_full_name", self.by_full_name.stats()),
+        ]
     }
 }
 
@@ -70,6 +249,294 @@ where
     }
 }
 
+#[derive(Debug, Clone)]
+struct Category {
+    id: u32,
+    parent: Option<u32>,
+}
+
+impl Selectable for Category {
+    type Indexer = CategoryIndexer;
+}
+
+struct CategoryIndexer {
+    tree: TreeIndex<Category, u32>,
+}
+
+impl Indexer<Category> for CategoryIndexer {
+    fn new() -> Self {
+        Self {
+            tree: TreeIndex::new(|category| category.id, |category| category.parent),
+        }
+    }
+
+    fn add(&mut self, row: Row<Category>, item: &Category) {
+        self.tree.add(row, item);
+    }
+
+    fn remove(&mut self, row: Row<Category>, item: &Category) {
+        self.tree.remove(row, item);
+    }
+}
+
+fn categories() -> Table<Category> {
+    vec![
+        Category {
+            id: 1,
+            parent: None,
+        },
+        Category {
+            id: 2,
+            parent: Some(1),
+        },
+        Category {
+            id: 3,
+            parent: Some(1),
+        },
+        Category {
+            id: 4,
+            parent: Some(2),
+        },
+        Category {
+            id: 5,
+            parent: Some(4),
+        },
+    ]
+    .into_iter()
+    .collect()
+}
+
+#[derive(Debug, Clone)]
+struct Reading {
+    at: SystemTime,
+}
+
+impl Selectable for Reading {
+    type Indexer = ReadingIndexer;
+}
+
+struct ReadingIndexer {
+    last_minute: WindowIndex<Reading>,
+    arrival: InsertionOrderIndex<Reading>,
+}
+
+impl Indexer<Reading> for ReadingIndexer {
+    fn new() -> Self {
+        Self {
+            last_minute: WindowIndex::new(|reading| reading.at, Duration::from_secs(60)),
+            arrival: InsertionOrderIndex::new(),
+        }
+    }
+
+    fn add(&mut self, row: Row<Reading>, item: &Reading) {
+        self.last_minute.add(row, item);
+        self.arrival.add(row, item);
+    }
+
+    fn remove(&mut self, row: Row<Reading>, item: &Reading) {
+        self.last_minute.remove(row, item);
+        self.arrival.remove(row, item);
+    }
+
+    fn before_update(&mut self, row: Row<Reading>, item: &Reading) {
+        self.last_minute.before_update(row, item);
+        self.arrival.before_update(row, item);
+    }
+
+    fn after_update(&mut self, row: Row<Reading>, item: &Reading) {
+        self.last_minute.after_update(row, item);
+        self.arrival.after_update(row, item);
+    }
+
+    fn before_update_many(&mut self, items: &[(Row<Reading>, &Reading)]) {
+        self.last_minute.before_update_many(items);
+        self.arrival.before_update_many(items);
+    }
+
+    fn after_update_many(&mut self, items: &[(Row<Reading>, &Reading)]) {
+        self.last_minute.after_update_many(items);
+        self.arrival.after_update_many(items);
+    }
+
+    fn advance(&mut self, now: SystemTime) {
+        self.last_minute.advance(now);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Account {
+    tenant: u32,
+    email: String,
+}
+
+impl Selectable for Account {
+    type Indexer = AccountIndexer;
+}
+
+struct AccountIndexer {
+    by_tenant_email: UniqueIndex<Account, (u32, String)>,
+}
+
+impl Indexer<Account> for AccountIndexer {
+    fn new() -> Self {
+        Self {
+            by_tenant_email: UniqueIndex::new(|account| (account.tenant, account.email.clone())),
+        }
+    }
+
+    fn add(&mut self, row: Row<Account>, item: &Account) {
+        self.by_tenant_email.add(row, item);
+    }
+
+    fn remove(&mut self, row: Row<Account>, item: &Account) {
+        self.by_tenant_email.remove(row, item);
+    }
+
+    fn conflict(&self, item: &Account) -> Option<Row<Account>> {
+        self.by_tenant_email.conflict(item)
+    }
+}
+
+enum PersonEvent {
+    Joined(Person),
+    Married { id: u32, last_name: &'static str },
+    HadBirthday { id: u32 },
+}
+
+impl Event<Person> for PersonEvent {
+    fn apply(self, table: &mut Table<Person>) {
+        match self {
+            PersonEvent::Joined(person) => {
+                table.insert(person);
+            }
+            PersonEvent::Married { id, last_name } => {
+                let row = table.indexer().by_id.get(&id);
+                table
+                    .entry(row)
+                    .and_modify(|person| person.last_name = last_name.to_string());
+            }
+            PersonEvent::HadBirthday { id } => {
+                let row = table.indexer().by_id.get(&id);
+                table.entry(row).and_modify(|person| person.age += 1);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Price {
+    sku: &'static str,
+    cents: u32,
+    valid_from: u32,
+    valid_to: Option<u32>,
+}
+
+impl Selectable for Price {
+    type Indexer = PriceIndexer;
+}
+
+struct PriceIndexer {
+    validity: ValidityIndex<Price, u32>,
+}
+
+impl Indexer<Price> for PriceIndexer {
+    fn new() -> Self {
+        Self {
+            validity: ValidityIndex::new(
+                |price| &price.valid_from,
+                |price| price.valid_to.as_ref(),
+            ),
+        }
+    }
+
+    fn add(&mut self, row: Row<Price>, item: &Price) {
+        self.validity.add(row, item);
+    }
+
+    fn remove(&mut self, row: Row<Price>, item: &Price) {
+        self.validity.remove(row, item);
+    }
+
+    fn before_update(&mut self, row: Row<Price>, item: &Price) {
+        self.validity.before_update(row, item);
+    }
+
+    fn after_update(&mut self, row: Row<Price>, item: &Price) {
+        self.validity.after_update(row, item);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Document {
+    id: u32,
+    title: String,
+}
+
+impl SharedSelectable for Document {
+    type Indexer = DocumentIndexer;
+}
+
+struct DocumentIndexer {
+    by_title: DiscreteIndex<Arc<Document>, String>,
+}
+
+impl Indexer<Arc<Document>> for DocumentIndexer {
+    fn new() -> Self {
+        Self {
+            by_title: DiscreteIndex::new(|document| &document.title),
+        }
+    }
+
+    fn add(&mut self, row: Row<Arc<Document>>, item: &Arc<Document>) {
+        self.by_title.add(row, item);
+    }
+
+    fn remove(&mut self, row: Row<Arc<Document>>, item: &Arc<Document>) {
+        self.by_title.remove(row, item);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Attachment {
+    name: String,
+    header: [u8; 512],
+}
+
+impl BoxedSelectable for Attachment {
+    type Indexer = AttachmentIndexer;
+}
+
+struct AttachmentIndexer {
+    by_name: UniqueIndex<Box<Attachment>, String>,
+}
+
+impl Indexer<Box<Attachment>> for AttachmentIndexer {
+    fn new() -> Self {
+        Self {
+            by_name: UniqueIndex::new(|attachment| attachment.name.clone()),
+        }
+    }
+
+    fn add(&mut self, row: Row<Box<Attachment>>, item: &Box<Attachment>) {
+        self.by_name.add(row, item);
+    }
+
+    fn remove(&mut self, row: Row<Box<Attachment>>, item: &Box<Attachment>) {
+        self.by_name.remove(row, item);
+    }
+}
+
+fn at(seconds: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)
+}
+
+fn readings() -> Table<Reading> {
+    [10, 50, 90, 130]
+        .iter()
+        .map(|&s| Reading { at: at(s) })
+        .collect()
+}
+
 // writing methods
 impl Query<Person, &mut Table<Person>> {
     fn increase_age(&mut self) {
@@ -77,6 +544,13 @@ impl Query<Person, &mut Table<Person>> {
     }
 }
 
+impl Table<Person> {
+    fn entry_by_id(&mut self, id: u32) -> Entry<'_, Person> {
+        let row = self.indexer().by_id.get(&id);
+        self.entry(row)
+    }
+}
+
 fn people() -> Table<Person> {
     let mut table = Table::in_memory();
 
@@ -142,6 +616,2119 @@ fn can_iterate() {
     assert_eq!(babies[0].id, 3);
 }
 
+#[test]
+fn can_iterate_by_reference() {
+    let people = people();
+    let adults: Vec<&Person> = people.select().adults().iter_ref().collect();
+
+    assert_eq!(adults.len(), 2);
+    assert_eq!(adults[0].first_name, "Aleksei");
+    assert_eq!(adults[1].first_name, "Polina");
+}
+
+#[test]
+fn borrowed_results_outlive_the_query() {
+    let people = people();
+    let olivia = people.select().by_id(3).first_ref();
+
+    assert_eq!(olivia.unwrap().age, 0);
+}
+
+#[test]
+fn can_select_unindexed_things() {
+    let mut things = Table::in_memory();
+    things.insert(UnindexedThing { id: 1 });
+
+    assert_eq!(things.select().count(), 1);
+    assert_eq!(things.select().first().unwrap().id, 1);
+}
+
+#[test]
+fn can_stream_in_batches() {
+    let mut people = people();
+    let mut stream = people.select().stream(2);
+
+    let first = stream.next_batch(&people).unwrap();
+    assert_eq!(first.len(), 2);
+    assert_eq!(first[0].id, 1);
+    assert_eq!(first[1].id, 2);
+
+    // the table is free to change between batches
+    people.update().by_id(3).apply(|p| p.age = 1);
+
+    let second = stream.next_batch(&people).unwrap();
+    assert_eq!(second.len(), 1);
+    assert_eq!(second[0].age, 1);
+
+    assert_eq!(stream.remaining(), 0);
+    assert!(stream.next_batch(&people).is_none());
+}
+
+#[test]
+fn can_skip_reused_rows_when_streaming() {
+    let mut cache = people().with_eviction(3, Eviction::Oldest);
+    let mut stream = cache.select().by_id(3).stream(1);
+    for id in 97..100 {
+        cache.insert(Person {
+            id,
+            first_name: "Olga".to_string(),
+            last_name: "Zhuravleva".to_string(),
+            age: 30,
+        });
+    }
+
+    assert_eq!(stream.next_batch(&cache), Some(vec![]));
+    assert_eq!(stream.skipped(), 1);
+    assert!(stream.next_batch(&cache).is_none());
+
+    let mut people = people();
+    let mut stream = people.select().stream(1);
+    assert_eq!(stream.next_batch(&people).unwrap()[0].id, 1);
+
+    let selection = people.select().by_id(1).selection().clone();
+    people.split_off(&selection);
+    assert!(stream.next_batch(&people).is_none());
+    assert_eq!(stream.skipped(), 2);
+}
+
+#[test]
+fn can_iterate_table_directly() {
+    let people = people();
+
+    let ages: Vec<u8> = (&people).into_iter().map(|p| p.age).collect();
+    assert_eq!(ages, vec![28, 32, 0]);
+
+    let ids: Vec<u32> = people.into_iter().map(|p| p.id).collect();
+    assert_eq!(ids, vec![1, 2, 3]);
+}
+
+#[test]
+fn can_collect_into_table() {
+    let adults: Table<Person> = people().into_iter().filter(|p| p.age >= 18).collect();
+
+    assert_eq!(adults.len(), 2);
+    assert_eq!(adults.select().adults().count(), 2);
+    assert_eq!(
+        adults
+            .select()
+            .by_last_name("Zhuravleva")
+            .first()
+            .unwrap()
+            .id,
+        2
+    );
+}
+
+#[test]
+fn can_extend_table() {
+    let mut people = people();
+    people.extend(vec![Person {
+        id: 4,
+        first_name: "Ivan".to_string(),
+        last_name: "Voronov".to_string(),
+        age: 60,
+    }]);
+
+    assert_eq!(people.len(), 4);
+    assert_eq!(people.select().by_last_name("Voronov").count(), 2);
+}
+
+#[test]
+fn tables_with_same_contents_are_equal() {
+    let mut changed = people();
+    assert!(changed == people());
+
+    changed.update().by_id(3).apply(|p| p.age = 1);
+    assert!(changed != people());
+}
+
+#[test]
+fn cloned_tables_are_independent() {
+    let people = people();
+    let mut copy = people.clone();
+    assert!(copy == people);
+
+    copy.update()
+        .by_id(1)
+        .apply(|p| p.last_name = "Smith".to_string());
+
+    assert_eq!(copy.select().by_last_name("Smith").count(), 1);
+    assert_eq!(copy.select().by_last_name("Voronov").count(), 0);
+    assert_eq!(people.select().by_last_name("Smith").count(), 0);
+    assert_eq!(people.select().by_last_name("Voronov").count(), 1);
+}
+
+#[test]
+fn can_format_results_as_table() {
+    let people = people();
+    let output = people.select().adults().format_table();
+
+    assert_eq!(
+        output,
+        "id | first_name | last_name  | age\n\
+         ---+------------+------------+----\n\
+         1  | Aleksei    | Voronov    | 28\n\
+         2  | Polina     | Zhuravleva | 32\n"
+    );
+}
+
+#[test]
+fn can_index_table_by_row() {
+    let people = people();
+    let row = people.indexer().by_id.get(&2).unwrap();
+
+    assert_eq!(people[row].first_name, "Polina");
+}
+
+#[test]
+#[should_panic(expected = "row 3 is out of bounds for a table of 3 items")]
+fn indexing_by_missing_row_panics() {
+    let people = people();
+    let _ = &people[unsafe { Row::from_index(3) }];
+}
+
+#[test]
+fn entry_inserts_missing_items() {
+    let mut people = people();
+    let entry = people.entry_by_id(4).or_insert_with(|| Person {
+        id: 4,
+        first_name: "Ivan".to_string(),
+        last_name: "Voronov".to_string(),
+        age: 60,
+    });
+
+    assert_eq!(entry.get().unwrap().first_name, "Ivan");
+    assert_eq!(people.len(), 4);
+    assert_eq!(people.select().by_last_name("Voronov").count(), 2);
+}
+
+#[test]
+fn entry_modifies_existing_items() {
+    let mut people = people();
+    people
+        .entry_by_id(1)
+        .or_insert_with(|| unreachable!())
+        .and_modify(|p| p.last_name = "Smith".to_string());
+
+    assert_eq!(people.len(), 3);
+    assert_eq!(people.select().by_last_name("Smith").first().unwrap().id, 1);
+}
+
+#[test]
+fn entry_treats_stale_rows_as_vacant() {
+    let mut people = people();
+    let stale = people.indexer().by_id.get(&3);
+    let zhuravlevas = people.indexer().by_last_name.get("Zhuravleva").clone();
+    people.split_off(&zhuravlevas);
+
+    let entry = people.entry(stale).and_modify(|p| p.age += 1);
+    assert!(entry.row().is_none());
+    assert!(entry.get().is_none());
+    let row = entry.or_insert_with(|| Person {
+        id: 4,
+        first_name: "Ivan".to_string(),
+        last_name: "Voronov".to_string(),
+        age: 60,
+    });
+    assert_eq!(row.get().unwrap().id, 4);
+    assert_eq!(people.len(), 2);
+}
+
+#[test]
+fn indexes_accept_custom_hashers() {
+    type Hasher = BuildHasherDefault<DefaultHasher>;
+
+    let mut by_id: UniqueIndex<Person, u32, Hasher> =
+        UniqueIndex::with_hasher(|person| person.id, Hasher::default());
+    let mut by_last_name: DiscreteIndex<Person, String, Hasher> =
+        DiscreteIndex::with_hasher(|person| &person.last_name, Hasher::default());
+
+    for (index, person) in people().into_iter().enumerate() {
+        let row = unsafe { Row::from_index(index) };
+        by_id.add(row, &person);
+        by_last_name.add(row, &person);
+    }
+
+    assert_eq!(by_id.get(&3).map(Row::as_index), Some(2));
+    assert_eq!(by_last_name.get("Zhuravleva").len(), 2);
+}
+
+#[derive(Default)]
+struct Counters {
+    inserts: AtomicU64,
+    updated_rows: AtomicU64,
+    queried_rows: AtomicU64,
+}
+
+impl Metrics for Counters {
+    fn inserted(&self, _duration: Duration) {
+        self.inserts.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn updated(&self, rows: u64, _duration: Duration) {
+        self.updated_rows.fetch_add(rows, Ordering::SeqCst);
+    }
+
+    fn queried(&self, rows: u64, _duration: Duration) {
+        self.queried_rows.fetch_add(rows, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn metrics_observe_operations() {
+    let counters = Arc::new(Counters::default());
+    let mut people = Table::in_memory().with_metrics(counters.clone());
+    people.extend(self::people());
+
+    let _: Vec<_> = people.select().by_last_name("Zhuravleva").collect();
+    people.update().adults().increase_age();
+
+    assert_eq!(counters.inserts.load(Ordering::SeqCst), 3);
+    assert_eq!(counters.queried_rows.load(Ordering::SeqCst), 2);
+    assert_eq!(counters.updated_rows.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn metrics_observe_every_read() {
+    let counters = Arc::new(Counters::default());
+    let mut people = Table::in_memory().with_metrics(counters.clone());
+    people.extend(self::people());
+    let queried = || counters.queried_rows.load(Ordering::SeqCst);
+
+    assert_eq!(people.select().adults().iter().count(), 2);
+    assert_eq!(queried(), 2);
+    people.select().first_ref();
+    people.select().first_cow();
+    assert_eq!(queried(), 4);
+    let _: Vec<_> = people.select().pluck(|p| &p.id).collect();
+    let _: Vec<_> = people.select().project(|p| p.age).take(1).collect();
+    assert_eq!(queried(), 8);
+    let _: Vec<_> = people
+        .select()
+        .by_id(1)
+        .pluck_ref(|p| &p.last_name)
+        .collect();
+    assert_eq!(queried(), 9);
+
+    let mut stream = people.select().stream(2);
+    while stream.next_batch(&people).is_some() {}
+    assert_eq!(queried(), 12);
+}
+
+#[test]
+fn slow_queries_are_reported() {
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let log = reported.clone();
+    let slow = SlowQueries::new(Duration::from_secs(60), 1, move |query| {
+        log.lock().unwrap().push(query.rows)
+    });
+
+    let mut people = Table::in_memory().with_metrics(Arc::new(slow));
+    people.extend(self::people());
+
+    let _: Vec<_> = people.select().by_id(1).collect();
+    let _: Vec<_> = people.select().adults().collect();
+
+    assert_eq!(*reported.lock().unwrap(), vec![2]);
+}
+
+#[test]
+fn query_durations_start_at_each_read() {
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let log = reported.clone();
+    let slow = SlowQueries::new(Duration::from_millis(50), u64::MAX, move |query| {
+        log.lock().unwrap().push(query.rows)
+    });
+
+    let mut people = Table::in_memory().with_metrics(Arc::new(slow));
+    people.extend(self::people());
+
+    let query = people.select();
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(query.iter().count(), 3);
+    assert_eq!(query.iter().count(), 3);
+
+    assert!(reported.lock().unwrap().is_empty());
+}
+
+#[test]
+fn can_report_table_stats() {
+    let mut people = people();
+    people
+        .update()
+        .by_last_name("Voronov")
+        .apply(|p| p.last_name = "Zhuravleva".to_string());
+
+    let stats = people.stats();
+    let index = |name| stats.indexes.iter().find(|(n, _)| *n == name).unwrap().1;
+
+    assert_eq!(stats.rows, 3);
+    assert!(stats.item_bytes >= 3 * std::mem::size_of::<Person>());
+    assert_eq!(index("by_id"), IndexStats { keys: 3, rows: 3 });
+    assert_eq!(index("by_last_name"), IndexStats { keys: 1, rows: 3 });
+    assert_eq!(index("by_age"), IndexStats { keys: 3, rows: 3 });
+    assert_eq!(index("adults"), IndexStats { keys: 1, rows: 2 });
+}
+
+#[test]
+fn can_page_through_sorted_index() {
+    let mut people = people();
+    people.insert(Person {
+        id: 4,
+        first_name: "Ivan".to_string(),
+        last_name: "Voronov".to_string(),
+        age: 28,
+    });
+    let by_age = &people.indexer().by_age;
+
+    let first = by_age.page_after(None, 2);
+    assert_eq!(
+        first.iter().map(|&row| people[row].id).collect::<Vec<_>>(),
+        vec![3, 1]
+    );
+
+    let last = *first.last().unwrap();
+    let second = by_age.page_after(Some((&people[last].age, last)), 2);
+    assert_eq!(
+        second.iter().map(|&row| people[row].id).collect::<Vec<_>>(),
+        vec![4, 2]
+    );
+
+    let last = *second.last().unwrap();
+    assert!(by_age
+        .page_after(Some((&people[last].age, last)), 2)
+        .is_empty());
+}
+
+#[test]
+fn can_page_through_query_results() {
+    let people = people();
+    let page = people
+        .select()
+        .adults()
+        .page_after(&people.indexer().by_age, None, 1);
+
+    assert_eq!(page.len(), 1);
+    assert_eq!(people[page[0]].id, 1);
+}
+
+#[test]
+fn can_page_through_selective_query_results() {
+    let mut people = people();
+    people.extend((4..20).map(|id| Person {
+        id,
+        first_name: "Ivan".to_string(),
+        last_name: "Voronov".to_string(),
+        age: 20 + (id % 7) as u8,
+    }));
+    let by_age = &people.indexer().by_age;
+
+    let mut query = people.select();
+    query.by_last_name("Zhuravleva");
+    let first = query.page_after(by_age, None, 1);
+    assert_eq!(first, by_age.page_within(query.selection(), None, 1));
+    assert_eq!(people[first[0]].id, 3);
+
+    let after = (&people[first[0]].age, first[0]);
+    let second = query.page_after(by_age, Some(after), 5);
+    assert_eq!(
+        second,
+        by_age.page_within(query.selection(), Some(after), 5)
+    );
+    assert_eq!(people[second[0]].id, 2);
+    assert_eq!(second.len(), 1);
+}
+
+#[test]
+fn sorted_index_drops_emptied_keys() {
+    let mut people = people();
+    people.update().by_id(3).apply(|p| p.age = 28);
+    assert_eq!(
+        people.indexer().by_age.stats(),
+        IndexStats { keys: 2, rows: 3 }
+    );
+    assert_eq!(people.indexer().by_age.range(..).len(), 3);
+}
+
+#[test]
+fn can_select_between() {
+    let people = people();
+    let by_age = &people.indexer().by_age;
+
+    let young: Vec<_> = people.select().between(by_age, 0..=28).collect();
+    assert_eq!(young.len(), 2);
+    assert_eq!(young[0].id, 1);
+    assert_eq!(young[1].id, 3);
+
+    let young_adults = people.select().adults().between(by_age, ..30).count();
+    assert_eq!(young_adults, 1);
+}
+
+#[test]
+fn can_select_by_prefix_and_suffix() {
+    let people = people();
+    let indexer = people.indexer();
+
+    let zhuravlevas: Vec<_> = people
+        .select()
+        .ends_with(&indexer.by_last_name_suffix, "eva")
+        .collect();
+    assert_eq!(zhuravlevas.len(), 2);
+
+    let olivia = people
+        .select()
+        .ends_with(&indexer.by_last_name_suffix, "eva")
+        .starts_with(&indexer.by_first_name_prefix, "Ol")
+        .first();
+    assert_eq!(olivia.unwrap().id, 3);
+
+    assert_eq!(
+        people
+            .select()
+            .starts_with(&indexer.by_first_name_prefix, "")
+            .count(),
+        3
+    );
+    assert_eq!(
+        people
+            .select()
+            .starts_with(&indexer.by_first_name_prefix, "X")
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn can_select_fuzzy_matches() {
+    let mut people = people();
+    let fuzzy = &people.indexer().by_last_name_fuzzy;
+
+    assert_eq!(
+        people.select().matches_within(fuzzy, "Voronv", 0).count(),
+        0
+    );
+    assert_eq!(
+        people.select().matches_within(fuzzy, "Voronv", 1).count(),
+        1
+    );
+    assert_eq!(
+        people
+            .select()
+            .matches_within(fuzzy, "Zhuravlev", 1)
+            .count(),
+        2
+    );
+    assert_eq!(
+        people.select().matches_within(fuzzy, "Voronova", 3).count(),
+        1
+    );
+
+    people
+        .update()
+        .by_id(1)
+        .apply(|p| p.last_name = "Smith".to_string());
+    let fuzzy = &people.indexer().by_last_name_fuzzy;
+    assert_eq!(
+        people.select().matches_within(fuzzy, "Voronv", 1).count(),
+        0
+    );
+    assert_eq!(people.select().matches_within(fuzzy, "Smyth", 1).count(), 1);
+}
+
+#[test]
+fn can_select_by_sound() {
+    let people = people();
+    let sound = &people.indexer().by_last_name_sound;
+
+    let voronov = people.select().sounds_like(sound, "Voronoff").first();
+    assert_eq!(voronov.unwrap().id, 1);
+
+    assert_eq!(people.select().sounds_like(sound, "Zhuravlyova").count(), 2);
+    assert_eq!(people.select().sounds_like(sound, "Smith").count(), 0);
+    assert_eq!(people.select().sounds_like(sound, "").count(), 0);
+
+    let mut people = people;
+    people
+        .update()
+        .by_id(1)
+        .apply(|p| p.last_name = "Smith".to_string());
+    let sound = &people.indexer().by_last_name_sound;
+    assert_eq!(people.select().sounds_like(sound, "Voronoff").count(), 0);
+    assert_eq!(sound.stats(), IndexStats { keys: 2, rows: 3 });
+}
+
+#[test]
+fn sound_index_skips_values_without_latin_letters() {
+    let mut people = people();
+    for (id, last_name) in [(4, "Воронов"), (5, "Журавлева"), (6, "123")].iter() {
+        people.insert(Person {
+            id: *id,
+            first_name: "Olga".to_string(),
+            last_name: last_name.to_string(),
+            age: 30,
+        });
+    }
+    let sound = &people.indexer().by_last_name_sound;
+
+    assert_eq!(people.select().sounds_like(sound, "Иванов").count(), 0);
+    assert_eq!(people.select().sounds_like(sound, "Voronoff").count(), 1);
+    assert_eq!(sound.stats(), IndexStats { keys: 2, rows: 3 });
+}
+
+#[derive(PartialEq, Eq, Hash)]
+enum Relation {
+    Parent,
+    Knows,
+}
+
+#[test]
+fn can_follow_edges() {
+    let people = people();
+    let row = |id| people.indexer().by_id.get(&id).unwrap();
+
+    let mut relations = EdgeIndex::new();
+    relations.connect(Relation::Parent, row(2), row(3));
+    relations.connect(Relation::Parent, row(1), row(3));
+    relations.connect(Relation::Knows, row(1), row(2));
+
+    let children: Vec<_> = people
+        .select()
+        .only(relations.neighbors(&Relation::Parent, row(1)).clone())
+        .collect();
+    assert_eq!(children.len(), 1);
+    assert_eq!(children[0].id, 3);
+
+    relations.disconnect(&Relation::Parent, row(1), row(3));
+    assert!(relations.neighbors(&Relation::Parent, row(1)).is_empty());
+    assert!(relations.neighbors(&Relation::Knows, row(3)).is_empty());
+}
+
+#[test]
+fn can_traverse_multiple_hops() {
+    let people = people();
+    let row = |id| people.indexer().by_id.get(&id).unwrap();
+
+    let mut knows = EdgeIndex::new();
+    knows.connect((), row(1), row(2));
+    knows.connect((), row(2), row(3));
+    knows.connect((), row(3), row(1));
+
+    let start = Selection::of_row(row(1));
+    assert_eq!(knows.reachable_within(&(), &start, 0).len(), 0);
+    assert_eq!(knows.reachable_within(&(), &start, 1).len(), 1);
+    assert_eq!(knows.reachable_within(&(), &start, 2).len(), 2);
+    assert_eq!(knows.reachable_within(&(), &start, 10).len(), 3);
+}
+
+#[test]
+fn can_connect_rows_of_different_tables() {
+    let people = people();
+    let mut things = Table::in_memory();
+    let thing = things.insert(UnindexedThing { id: 7 });
+
+    let mut owns: EdgeIndex<Person, UnindexedThing> = EdgeIndex::new();
+    let owner = people.indexer().by_id.get(&2).unwrap();
+    owns.connect((), owner, thing);
+
+    let owned = owns.neighbors_of(&(), people.select().by_last_name("Zhuravleva").selection());
+    assert_eq!(things.select().only(owned).first().unwrap().id, 7);
+}
+
+#[test]
+fn can_navigate_hierarchy() {
+    let categories = categories();
+    let tree = &categories.indexer().tree;
+    let row = |index| unsafe { Row::from_index(index) };
+    let ids = |selection: &Selection<Category>| -> Vec<u32> {
+        categories
+            .select()
+            .only(selection.clone())
+            .iter()
+            .map(|c| c.id)
+            .collect()
+    };
+
+    assert_eq!(ids(tree.children_of(row(0))), vec![2, 3]);
+    assert_eq!(ids(&tree.descendants_of(row(0))), vec![2, 3, 4, 5]);
+    assert_eq!(ids(&tree.descendants_of(row(1))), vec![4, 5]);
+    assert_eq!(ids(&tree.ancestors_of(row(4))), vec![1, 2, 4]);
+    assert!(tree.ancestors_of(row(0)).is_empty());
+}
+
+#[test]
+fn hierarchy_follows_updates() {
+    let mut categories = categories();
+    categories
+        .update()
+        .only_row(unsafe { Row::from_index(3) })
+        .apply(|c| c.parent = Some(3));
+
+    let tree = &categories.indexer().tree;
+    let row = |index| unsafe { Row::from_index(index) };
+    assert_eq!(tree.descendants_of(row(1)).len(), 0);
+    assert_eq!(tree.descendants_of(row(2)).len(), 2);
+    assert_eq!(tree.parent_of(row(3)), Some(row(2)));
+}
+
+#[test]
+fn can_select_time_windows() {
+    let readings = readings();
+    let index = &readings.indexer().last_minute;
+
+    let count = |now, seconds| {
+        readings
+            .select()
+            .in_window(index, at(now), Duration::from_secs(seconds))
+            .count()
+    };
+    assert_eq!(count(100, 60), 2);
+    assert_eq!(count(100, 100), 3);
+    assert_eq!(count(5, 60), 0);
+
+    let mut readings = readings;
+    readings.update().apply(|reading| reading.at = at(10));
+    let stats = readings.indexer().last_minute.stats();
+    assert_eq!(stats, IndexStats { keys: 1, rows: 4 });
+}
+
+#[test]
+fn can_advance_rolling_window() {
+    let mut readings = readings();
+    let current = |readings: &Table<Reading>| -> Vec<u64> {
+        readings
+            .select()
+            .in_current_window(&readings.indexer().last_minute)
+            .iter()
+            .map(|r| r.at.duration_since(at(0)).unwrap().as_secs())
+            .collect()
+    };
+    assert!(current(&readings).is_empty());
+
+    readings.advance_window(at(60));
+    assert_eq!(current(&readings), vec![10, 50]);
+
+    readings.advance_window(at(100));
+    assert_eq!(current(&readings), vec![50, 90]);
+
+    readings.insert(Reading { at: at(95) });
+    assert_eq!(current(&readings), vec![50, 90, 95]);
+
+    readings.advance_window(at(500));
+    assert!(current(&readings).is_empty());
+
+    readings.advance_window(at(140));
+    assert_eq!(current(&readings), vec![90, 130, 95]);
+}
+
+#[test]
+fn aggregates_are_maintained() {
+    let mut people = people();
+    let ages = &people.indexer().age_by_last_name;
+    assert_eq!(ages.sum("Zhuravleva"), 32);
+    assert_eq!(ages.count("Zhuravleva"), 2);
+    assert_eq!(ages.sum("Voronov"), 28);
+    assert_eq!(ages.total(), 60);
+
+    people.update().increase_age();
+    people
+        .update()
+        .by_id(1)
+        .apply(|p| p.last_name = "Zhuravleva".to_string());
+
+    let ages = &people.indexer().age_by_last_name;
+    assert_eq!(ages.sum("Zhuravleva"), 63);
+    assert_eq!(ages.count("Voronov"), 0);
+    assert_eq!(ages.groups().count(), 1);
+}
+
+#[test]
+fn can_aggregate_without_groups() {
+    let mut total = AggregateIndex::new(|person: &Person| person.age as f64);
+    for (index, person) in people().into_iter().enumerate() {
+        total.add(unsafe { Row::from_index(index) }, &person);
+    }
+
+    assert_eq!(total.sum(&()), 60.0);
+    assert_eq!(total.count(&()), 3);
+}
+
+#[test]
+fn min_and_max_are_maintained() {
+    let mut people = people();
+    assert_eq!(people.indexer().age_range.min(), Some(&0));
+    assert_eq!(people.indexer().age_range.max(), Some(&32));
+
+    people.update().by_id(2).apply(|p| p.age = 20);
+    people.update().by_id(3).apply(|p| p.age = 1);
+    assert_eq!(people.indexer().age_range.min(), Some(&1));
+    assert_eq!(people.indexer().age_range.max(), Some(&28));
+
+    let empty: Table<Person> = Table::in_memory();
+    assert_eq!(empty.indexer().age_range.min(), None);
+}
+
+#[test]
+fn can_compute_quantiles() {
+    let mut people = people();
+    let ages = &people.indexer().age_quantiles;
+    assert_eq!(ages.quantile(0.0), Some(0));
+    assert_eq!(ages.quantile(0.5), Some(28));
+    assert_eq!(ages.quantile(0.95), Some(32));
+
+    let mut adults = people.select();
+    adults.adults();
+    assert_eq!(ages.quantile_of(adults.selection(), 0.5), Some(28));
+    assert_eq!(ages.quantile_of(&Selection::empty(), 0.5), None);
+
+    people.update().by_id(1).apply(|p| p.age = 40);
+    let ages = &people.indexer().age_quantiles;
+    assert_eq!(ages.quantile(0.5), Some(32));
+    assert_eq!(ages.quantile(1.0), Some(40));
+}
+
+#[test]
+fn can_maintain_a_sample() {
+    let mut things = Table::in_memory().with_sample(5);
+    for id in 0..3 {
+        things.insert(UnindexedThing { id });
+    }
+    assert_eq!(things.sample_view().unwrap().count(), 3);
+
+    things.extend((3..1000).map(|id| UnindexedThing { id }));
+    let sample: Vec<_> = things.sample_view().unwrap().collect();
+    assert_eq!(sample.len(), 5);
+    assert!(sample.windows(2).all(|pair| pair[0].id < pair[1].id));
+
+    assert!(people().sample_view().is_none());
+    assert_eq!(people().with_sample(2).sample_view().unwrap().count(), 2);
+}
+
+#[test]
+fn can_find_duplicates() {
+    let mut people = people();
+    people.insert(Person {
+        id: 4,
+        first_name: "Aleksei".to_string(),
+        last_name: "Smith".to_string(),
+        age: 32,
+    });
+
+    let by_first_name = people.duplicates_by(|p| p.first_name.clone());
+    assert_eq!(by_first_name.len(), 1);
+    assert_eq!(by_first_name[0].len(), 2);
+
+    let by_age = people.duplicates_by(|p| p.age);
+    assert_eq!(by_age.len(), 1);
+    let ids: Vec<_> = people
+        .select()
+        .only(by_age[0].clone())
+        .iter()
+        .map(|p| p.id)
+        .collect();
+    assert_eq!(ids, vec![2, 4]);
+
+    assert!(people.duplicates_by(|p| p.id).is_empty());
+
+    let by_last_name: Vec<_> = people.indexer().by_last_name.duplicates().collect();
+    assert_eq!(by_last_name.len(), 1);
+    assert_eq!(by_last_name[0].0, "Zhuravleva");
+}
+
+#[test]
+fn can_select_rows_related_to_another_table() {
+    let people = people();
+    let mut owned = Table::in_memory();
+    owned.extend(vec![UnindexedThing { id: 1 }, UnindexedThing { id: 3 }]);
+    let by_id = &people.indexer().by_id;
+
+    let owners: Vec<_> = people
+        .select()
+        .where_in(by_id, &owned.select(), |thing| thing.id)
+        .iter()
+        .map(|p| p.id)
+        .collect();
+    assert_eq!(owners, vec![1, 3]);
+
+    let without_things: Vec<_> = people
+        .select()
+        .where_not_in(by_id, &owned.select(), |thing| thing.id)
+        .iter()
+        .map(|p| p.id)
+        .collect();
+    assert_eq!(without_things, vec![2]);
+
+    let adult_owners = people
+        .select()
+        .adults()
+        .where_in(by_id, &owned.select(), |thing| thing.id)
+        .count();
+    assert_eq!(adult_owners, 1);
+}
+
+#[test]
+fn can_group_and_aggregate() {
+    let people = people();
+    let groups = people
+        .select()
+        .group_by(&people.indexer().by_last_name)
+        .aggregate((
+            count(),
+            sum(|p: &Person| p.age as u32),
+            min(|p: &Person| p.age),
+            max(|p: &Person| p.first_name.clone()),
+        ));
+
+    assert_eq!(groups.len(), 2);
+    assert_eq!(
+        groups["Zhuravleva"],
+        (2, 32, Some(0), Some("Polina".to_string()))
+    );
+    assert_eq!(
+        groups["Voronov"],
+        (1, 28, Some(28), Some("Aleksei".to_string()))
+    );
+
+    let adults = people
+        .select()
+        .adults()
+        .group_by(&people.indexer().by_last_name)
+        .aggregate(count());
+    assert_eq!(adults["Zhuravleva"], 1);
+}
+
+#[test]
+fn can_compose_queries_with_macro() {
+    let people = people();
+
+    let zhuravleva_adults: Vec<_> = query!(people, by_last_name == "Zhuravleva" && adults)
+        .iter()
+        .map(|p| p.id)
+        .collect();
+    assert_eq!(zhuravleva_adults, vec![2]);
+
+    let voronovs_or_children: Vec<_> = query!(
+        people,
+        by_last_name == "Voronov"
+            || between(&people.indexer().by_age, ..18)
+            || by_id == 2 && adults
+    )
+    .iter()
+    .map(|p| p.id)
+    .collect();
+    assert_eq!(voronovs_or_children, vec![1, 2, 3]);
+}
+
+#[test]
+fn can_apply_updates_atomically() {
+    let mut people = people();
+    people
+        .update()
+        .by_last_name("Zhuravleva")
+        .apply_atomic(|p| p.age += 1);
+    assert_eq!(
+        people
+            .select()
+            .by_last_name("Zhuravleva")
+            .first()
+            .unwrap()
+            .age,
+        33
+    );
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        people.update().apply_atomic(|p| {
+            if p.age == 1 {
+                panic!("cannot update a baby");
+            }
+            p.age += 1;
+        })
+    }));
+
+    assert!(result.is_err());
+    let ages: Vec<_> = people.select().iter().map(|p| p.age).collect();
+    assert_eq!(ages, vec![28, 33, 1]);
+    assert_eq!(
+        people
+            .select()
+            .between(&people.indexer().by_age, 29..)
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn can_apply_updates_in_batches() {
+    let mut people = people();
+    people
+        .update()
+        .by_last_name("Zhuravleva")
+        .apply_batched(|p| p.last_name = "Voronova".to_string());
+
+    assert_eq!(people.select().by_last_name("Zhuravleva").count(), 0);
+    assert_eq!(people.select().by_last_name("Voronova").count(), 2);
+    let stats = people.indexer().by_last_name.stats();
+    assert_eq!((stats.keys, stats.rows), (2, 3));
+
+    people.update().apply_batched(|p| p.age = p.age.max(18));
+
+    let indexer = people.indexer();
+    assert_eq!(indexer.adults.get().len(), 3);
+    assert_eq!(indexer.adults.get_false().len(), 0);
+    assert_eq!(indexer.by_age.get(&0).len(), 0);
+    assert_eq!(indexer.by_age.get(&18).len(), 1);
+    assert_eq!(indexer.by_age.stats().keys, 3);
+    assert_eq!(indexer.seniority.stats().keys, 3);
+    assert_eq!(indexer.by_id.get(&3).map(Row::as_index), Some(2));
+    assert_eq!(indexer.by_id.len(), 3);
+}
+
+#[test]
+fn can_apply_batched_updates_without_losing_row_state() {
+    let mut readings = readings();
+    let first: Selection<Reading> = readings.indexer().arrival.rows().take(2).collect();
+    readings.update().and(&first).apply_batched(|_| {});
+
+    let arrival = &readings.indexer().arrival;
+    let times: Vec<_> = arrival.rows().map(|row| readings[row].at).collect();
+    assert_eq!(times, vec![at(10), at(50), at(90), at(130)]);
+    let sequences: Vec<_> = arrival.rows().map(|row| arrival.sequence(row)).collect();
+    assert_eq!(sequences, vec![Some(0), Some(1), Some(2), Some(3)]);
+}
+
+#[test]
+fn can_maintain_indexes_in_bulk() {
+    let people = people();
+    let everyone = people.select();
+    let batch: Vec<_> = everyone
+        .selection()
+        .rows()
+        .zip(everyone.iter_ref())
+        .collect();
+
+    let mut by_last_name = DiscreteIndex::new(|p: &Person| &p.last_name);
+    by_last_name.add_many(&batch);
+    assert_eq!(by_last_name.get("Zhuravleva").len(), 2);
+
+    by_last_name.remove_many(&batch[1..]);
+    assert!(by_last_name.get("Zhuravleva").is_empty());
+    assert_eq!(by_last_name.get("Voronov").len(), 1);
+
+    let mut adults = BooleanIndex::new(|p: &Person| p.age >= 18);
+    adults.add_many(&batch);
+    assert_eq!(adults.get().len(), 2);
+}
+
+#[test]
+fn can_update_index_entries_in_place() {
+    let people = people();
+    let polina = people.select().by_id(2).first().unwrap();
+    let row = people.indexer().by_id.get(&2).unwrap();
+
+    let mut by_last_name = DiscreteIndex::new(|p: &Person| &p.last_name);
+    by_last_name.add(row, &polina);
+
+    let older = Person {
+        age: polina.age + 1,
+        ..polina.clone()
+    };
+    by_last_name.before_update(row, &polina);
+    by_last_name.after_update(row, &older);
+    assert!(by_last_name.get("Zhuravleva").contains(row));
+
+    let married = Person {
+        last_name: "Voronova".to_string(),
+        ..older.clone()
+    };
+    by_last_name.before_update(row, &older);
+    by_last_name.after_update(row, &married);
+    assert!(by_last_name.get("Zhuravleva").is_empty());
+    assert!(by_last_name.get("Voronova").contains(row));
+}
+
+#[test]
+fn can_insert_unique_items() {
+    let mut people = people();
+    let polina = people.select().by_id(2).first().unwrap();
+    let existing = people.indexer().by_id.get(&2).unwrap();
+
+    let conflict = people.insert_unique(polina.clone()).unwrap_err();
+    assert_eq!(conflict.row, existing);
+    assert_eq!(conflict.item, polina);
+    assert_eq!(people.insert_or_get(polina.clone()), existing);
+    assert_eq!(people.len(), 3);
+
+    let stranger = Person {
+        id: 4,
+        ..polina.clone()
+    };
+    let row = people.insert_unique(stranger.clone()).unwrap();
+    assert_eq!(people[row], stranger);
+    assert_eq!(people.insert_or_get(stranger), row);
+    assert_eq!(people.len(), 4);
+}
+
+#[test]
+fn can_apply_or_insert() {
+    let mut people = people();
+    let newborn = || Person {
+        id: 4,
+        first_name: "Ivan".to_string(),
+        last_name: "Voronov".to_string(),
+        age: 0,
+    };
+
+    people
+        .update()
+        .by_id(4)
+        .apply_or_insert(|p| p.age += 1, newborn);
+    assert_eq!(people.select().by_id(4).first().unwrap().age, 0);
+
+    people
+        .update()
+        .by_id(4)
+        .apply_or_insert(|p| p.age += 1, newborn);
+    assert_eq!(people.select().by_id(4).first().unwrap().age, 1);
+    assert_eq!(people.len(), 4);
+}
+
+#[test]
+fn can_apply_conditionally() {
+    let mut people = people();
+    let applied = people
+        .update()
+        .by_last_name("Zhuravleva")
+        .apply_if(|p| p.age < 18, |p| p.age = p.age.max(1));
+
+    assert_eq!(
+        applied,
+        Applied {
+            matched: 1,
+            changed: 1
+        }
+    );
+    assert_eq!(
+        people
+            .select()
+            .between(&people.indexer().by_age, ..1)
+            .count(),
+        0
+    );
+
+    let applied = people
+        .update()
+        .apply_if(|p| p.age < 18, |p| p.age = p.age.max(1));
+    assert_eq!(
+        applied,
+        Applied {
+            matched: 1,
+            changed: 0
+        }
+    );
+}
+
+#[test]
+fn can_collect_within_budget() {
+    let people = people();
+
+    let everyone = people.select().collect_within(Budget {
+        max_rows: Some(3),
+        max_duration: None,
+    });
+    assert_eq!(everyone.unwrap().len(), 3);
+
+    let truncated = people
+        .select()
+        .collect_within(Budget {
+            max_rows: Some(2),
+            max_duration: None,
+        })
+        .unwrap_err();
+    assert_eq!(truncated.reason, Exceeded::Rows);
+    assert_eq!(truncated.items.len(), 2);
+
+    let truncated = people
+        .select()
+        .collect_within(Budget {
+            max_rows: None,
+            max_duration: Some(Duration::from_secs(0)),
+        })
+        .unwrap_err();
+    assert_eq!(truncated.reason, Exceeded::Duration);
+    assert!(truncated.items.is_empty());
+}
+
+#[test]
+fn can_evict_least_recently_used_rows() {
+    let mut people = people().with_eviction(3, Eviction::LeastRecentlyUsed);
+    people.select().by_id(1).first();
+
+    let row = people.insert(Person {
+        id: 4,
+        first_name: "Ivan".to_string(),
+        last_name: "Voronov".to_string(),
+        age: 0,
+    });
+
+    assert_eq!(people.len(), 3);
+    assert_eq!(people.indexer().by_id.get(&4), Some(row));
+    assert_eq!(people.select().by_id(2).count(), 0);
+    assert_eq!(people.select().by_last_name("Voronov").count(), 2);
+    assert_eq!(people.select().by_last_name("Zhuravleva").count(), 1);
+
+    people.select().by_id(3).first();
+    people.insert(Person {
+        id: 5,
+        first_name: "Maria".to_string(),
+        last_name: "Voronova".to_string(),
+        age: 25,
+    });
+    assert_eq!(people.select().by_id(1).count(), 0);
+    assert_eq!(people.select().by_id(3).count(), 1);
+}
+
+#[test]
+fn can_keep_hot_rows_across_many_evictions() {
+    let mut people = people().with_eviction(3, Eviction::LeastRecentlyUsed);
+    for id in 4..20 {
+        people.select().by_id(2).first();
+        people.insert(Person {
+            id,
+            first_name: "Ivan".to_string(),
+            last_name: "Voronov".to_string(),
+            age: 0,
+        });
+    }
+
+    let mut ids: Vec<_> = people.select().iter().map(|p| p.id).collect();
+    ids.sort_unstable();
+    assert_eq!(ids, vec![2, 18, 19]);
+}
+
+#[test]
+fn can_overwrite_oldest_rows_when_full() {
+    let mut readings = readings().with_eviction(4, Eviction::Oldest);
+    let rows: Vec<_> = [140, 150, 160]
+        .iter()
+        .map(|&s| readings.insert(Reading { at: at(s) }).as_index())
+        .collect();
+
+    assert_eq!(rows, vec![0, 1, 2]);
+    assert_eq!(readings.len(), 4);
+
+    let mut times: Vec<_> = readings.select().iter().map(|r| r.at).collect();
+    times.sort();
+    assert_eq!(times, vec![at(130), at(140), at(150), at(160)]);
+
+    let row = readings.insert(Reading { at: at(170) });
+    assert_eq!(row.as_index(), 3);
+}
+
+#[test]
+fn can_evict_lowest_priority_rows() {
+    let mut people = people().with_eviction(3, Eviction::LowestPriority);
+    assert_eq!(
+        people.indexer().seniority.highest(),
+        people.indexer().by_id.get(&2)
+    );
+
+    let row = people.insert(Person {
+        id: 4,
+        first_name: "Ivan".to_string(),
+        last_name: "Voronov".to_string(),
+        age: 5,
+    });
+
+    assert_eq!(people.len(), 3);
+    assert_eq!(people.indexer().by_id.get(&3), None);
+    assert_eq!(people.indexer().seniority.lowest(), Some(row));
+
+    people.insert(Person {
+        id: 5,
+        first_name: "Maria".to_string(),
+        last_name: "Voronova".to_string(),
+        age: 25,
+    });
+    assert_eq!(people.indexer().by_id.get(&4), None);
+    assert_eq!(people.select().by_last_name("Zhuravleva").count(), 1);
+}
+
+#[test]
+fn evicts_oldest_rows_without_a_priority_victim() {
+    let mut readings = readings().with_eviction(4, Eviction::LowestPriority);
+    readings.insert(Reading { at: at(170) });
+    readings.insert(Reading { at: at(210) });
+
+    assert_eq!(readings.len(), 4);
+    let times: Vec<_> = readings.select().iter().map(|r| r.at).collect();
+    assert_eq!(times, vec![at(170), at(210), at(90), at(130)]);
+}
+
+#[test]
+fn can_track_row_access() {
+    assert!(people().access_stats().is_none());
+
+    let people = people().with_access_stats();
+    let before = SystemTime::now();
+    people
+        .select()
+        .by_last_name("Zhuravleva")
+        .collect::<Vec<_>>();
+    people.select().by_id(2).first_ref();
+
+    let stats = people.access_stats().unwrap();
+    let reads: Vec<_> = stats.iter().map(|(_, stats)| stats.reads).collect();
+    assert_eq!(reads, vec![0, 2, 1]);
+    assert_eq!(stats[0].1.last_read, None);
+    assert!(stats[1].1.last_read.unwrap() + Duration::from_secs(1) >= before);
+}
+
+#[test]
+fn can_invert_queries() {
+    let people = people();
+    let children: Vec<_> = people
+        .select()
+        .adults()
+        .invert()
+        .iter()
+        .map(|p| p.id)
+        .collect();
+    assert_eq!(children, vec![3]);
+
+    let everyone = people.select().none().invert().count();
+    assert_eq!(everyone, 3);
+}
+
+#[test]
+fn can_pluck_fields() {
+    let people = people();
+    let ages: Vec<u8> = people.select().adults().pluck(|p| &p.age).collect();
+    assert_eq!(ages, vec![28, 32]);
+
+    let mut zhuravlevas = people.select();
+    zhuravlevas.by_last_name("Zhuravleva");
+    let first_names: Vec<&str> = zhuravlevas.pluck_ref(|p| p.first_name.as_str()).collect();
+    assert_eq!(first_names, vec!["Polina", "Olivia Alekseevna"]);
+}
+
+#[test]
+fn can_project_tuples_of_fields() {
+    let people = people();
+    let names: Vec<_> = select_fields!(people.select().adults(), first_name, age).collect();
+    assert_eq!(
+        names,
+        vec![("Aleksei".to_string(), 28), ("Polina".to_string(), 32)]
+    );
+
+    let ids: Vec<_> = select_fields!(people.select().by_last_name("Zhuravleva"), id).collect();
+    assert_eq!(ids, vec![(2,), (3,)]);
+}
+
+#[test]
+fn can_retrieve_cows() {
+    let people = people();
+    let query = people.select();
+    let ages: Vec<_> = query.iter_cow().map(|p| p.age).collect();
+    assert_eq!(ages, vec![28, 32, 0]);
+
+    let mut polina = people.select().by_id(2).first_cow().unwrap();
+    assert!(matches!(polina, Cow::Borrowed(_)));
+    polina.to_mut().age += 1;
+    assert_eq!(polina.into_owned().age, 33);
+    assert_eq!(people.select().by_id(2).first().unwrap().age, 32);
+}
+
+#[test]
+fn can_store_shared_rows() {
+    let mut documents: Table<Arc<Document>> = (1..=2)
+        .map(|id| {
+            Arc::new(Document {
+                id,
+                title: "Draft".to_string(),
+            })
+        })
+        .collect();
+
+    let before = documents.select().first().unwrap();
+    assert!(Arc::ptr_eq(&before, &documents.select().first().unwrap()));
+
+    let first = documents
+        .indexer()
+        .by_title
+        .get("Draft")
+        .rows()
+        .next()
+        .unwrap();
+    documents
+        .update()
+        .only_row(first)
+        .apply_shared(|document| document.title = "Final".to_string());
+
+    assert_eq!(before.title, "Draft");
+    let after = documents.select().first().unwrap();
+    assert_eq!(after.title, "Final");
+    assert!(!Arc::ptr_eq(&before, &after));
+    assert_eq!(documents.indexer().by_title.get("Draft").len(), 1);
+    assert_eq!(documents.indexer().by_title.get("Final").len(), 1);
+}
+
+#[test]
+fn can_store_boxed_rows() {
+    let mut attachments = Table::in_memory();
+    let row = attachments.insert(Box::new(Attachment {
+        name: "scan.png".to_string(),
+        header: [0; 512],
+    }));
+
+    attachments
+        .update()
+        .only_row(row)
+        .apply(|attachment| attachment.name = "scan-1.png".to_string());
+
+    let renamed = attachments.indexer().by_name.get("scan-1.png");
+    assert_eq!(renamed, Some(row));
+    assert_eq!(attachments[row].header.len(), 512);
+}
+
+#[test]
+fn can_share_interned_keys() {
+    let mut interner = Interner::new();
+    let mut tags = DiscreteIndex::new(|tagged: &(u32, Arc<str>)| &tagged.1);
+    let tagged: Vec<_> = ["red", "green", "red"]
+        .iter()
+        .enumerate()
+        .map(|(id, &tag)| (id as u32, interner.intern(tag)))
+        .collect();
+    for (index, item) in tagged.iter().enumerate() {
+        tags.add(unsafe { Row::from_index(index) }, item);
+    }
+
+    assert_eq!(interner.len(), 2);
+    assert!(Arc::ptr_eq(&tagged[0].1, &tagged[2].1));
+    assert_eq!(tags.get("red").len(), 2);
+
+    drop(tags);
+    drop(tagged);
+    interner.shrink();
+    assert!(interner.is_empty());
+}
+
+#[test]
+fn can_adapt_index_representation() {
+    let people = people();
+    let polina = people.select().by_id(2).first().unwrap();
+    let row = |index| unsafe { Row::from_index(index) };
+
+    let mut by_last_name = AdaptiveIndex::new(|p: &Person| &p.last_name, 4);
+    let zhuravleva = "Zhuravleva".to_string();
+    for index in 0..4 {
+        by_last_name.add(row(index), &polina);
+    }
+    assert!(!by_last_name.is_bitmap(&zhuravleva));
+
+    by_last_name.add(row(4), &polina);
+    assert!(by_last_name.is_bitmap(&zhuravleva));
+    assert_eq!(by_last_name.get("Zhuravleva").len(), 5);
+
+    for index in 0..3 {
+        by_last_name.remove(row(index), &polina);
+    }
+    assert!(!by_last_name.is_bitmap(&zhuravleva));
+    let rows: Vec<_> = by_last_name.get("Zhuravleva").rows().collect();
+    assert_eq!(rows, vec![row(3), row(4)]);
+    assert_eq!(by_last_name.stats(), IndexStats { keys: 1, rows: 2 });
+
+    let mut by_last_name = AdaptiveIndex::new(|p: &Person| &p.last_name, 1);
+    by_last_name.add(row(0), &polina);
+    by_last_name.add(row(1), &polina);
+    assert!(by_last_name.is_bitmap(&zhuravleva));
+    by_last_name.remove(row(0), &polina);
+    by_last_name.remove(row(1), &polina);
+    assert_eq!(by_last_name.stats(), IndexStats { keys: 0, rows: 0 });
+}
+
+#[test]
+fn can_index_dense_integer_keys() {
+    let people = people();
+    let mut by_id = DenseIndex::new(|p: &Person| p.id);
+    let everyone = people.select();
+    for (row, person) in everyone.selection().rows().zip(everyone.iter_ref()) {
+        by_id.add(row, person);
+    }
+
+    assert_eq!(by_id.get(2), people.indexer().by_id.get(&2));
+    assert_eq!(by_id.get(0), None);
+    assert_eq!(by_id.get(100), None);
+    assert_eq!(by_id.stats(), IndexStats { keys: 3, rows: 3 });
+
+    let polina = people.select().by_id(2).first().unwrap();
+    by_id.remove(by_id.get(2).unwrap(), &polina);
+    assert_eq!(by_id.conflict(&polina), None);
+    assert_eq!(by_id.get(1), people.indexer().by_id.get(&1));
+}
+
+#[test]
+fn can_index_sparse_integer_keys() {
+    let people = people();
+    let mut by_id = DenseIndex::new(|p: &Person| u64::from(p.id) << 40);
+    let everyone = people.select();
+    for (row, person) in everyone.selection().rows().zip(everyone.iter_ref()) {
+        by_id.add(row, person);
+    }
+
+    assert_eq!(by_id.get(2 << 40), people.indexer().by_id.get(&2));
+    assert_eq!(by_id.get(2), None);
+    assert_eq!(by_id.stats(), IndexStats { keys: 3, rows: 3 });
+
+    let polina = people.select().by_id(2).first().unwrap();
+    by_id.remove(by_id.get(2 << 40).unwrap(), &polina);
+    assert_eq!(by_id.get(2 << 40), None);
+    assert_eq!(by_id.get(3 << 40), people.indexer().by_id.get(&3));
+}
+
+#[test]
+fn can_index_dense_discrete_keys() {
+    let people = people();
+    let mut by_age = DenseDiscreteIndex::new(|p: &Person| p.age / 10);
+    let everyone = people.select();
+    for (row, person) in everyone.selection().rows().zip(everyone.iter_ref()) {
+        by_age.add(row, person);
+    }
+
+    assert_eq!(by_age.get(2).len(), 1);
+    assert_eq!(by_age.get(3).len(), 1);
+    assert!(by_age.get(9).is_empty());
+    assert_eq!(by_age.stats(), IndexStats { keys: 3, rows: 3 });
+
+    let polina = people.select().by_id(2).first().unwrap();
+    let row = people.indexer().by_id.get(&2).unwrap();
+    by_age.before_update(row, &polina);
+    by_age.after_update(
+        row,
+        &Person {
+            age: 25,
+            ..polina.clone()
+        },
+    );
+    assert_eq!(by_age.get(2).len(), 2);
+    assert_eq!(by_age.stats(), IndexStats { keys: 2, rows: 3 });
+}
+
+#[test]
+fn can_partition_tables() {
+    let mut people = PartitionedTable::new(|p: &Person| p.age / 10);
+    for person in self::people() {
+        people.insert(person);
+    }
+    let (decade, row) = people.insert(Person {
+        id: 4,
+        first_name: "Ivan".to_string(),
+        last_name: "Voronov".to_string(),
+        age: 25,
+    });
+
+    assert_eq!(decade, 2);
+    assert_eq!(people.partition(&2).unwrap()[row].id, 4);
+    assert_eq!(people.keys().copied().collect::<Vec<_>>(), vec![0, 2, 3]);
+    assert_eq!(people.len(), 4);
+
+    let voronovs: Vec<_> = people
+        .select(1..)
+        .flat_map(|(_, mut query)| {
+            query.by_last_name("Voronov");
+            query.iter().map(|p| p.id).collect::<Vec<_>>()
+        })
+        .collect();
+    assert_eq!(voronovs, vec![1, 4]);
+}
+
+#[test]
+fn can_drop_partitions() {
+    let mut readings = PartitionedTable::new(|r: &Reading| {
+        r.at.duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            / 60
+    });
+    for reading in self::readings() {
+        readings.insert(reading);
+    }
+    assert_eq!(readings.keys().copied().collect::<Vec<_>>(), vec![0, 1, 2]);
+
+    let dropped = readings.drop_partition(&1).unwrap();
+    assert_eq!(dropped.len(), 1);
+    assert!(readings.drop_partition(&1).is_none());
+
+    assert_eq!(readings.drop_partitions_before(&2), 1);
+    assert_eq!(readings.keys().copied().collect::<Vec<_>>(), vec![2]);
+    assert_eq!(readings.len(), 1);
+}
+
+#[test]
+fn can_split_and_absorb_tables() {
+    let mut people = people();
+    let zhuravlevas = people.indexer().by_last_name.get("Zhuravleva").clone();
+
+    let mut split = people.split_off(&zhuravlevas);
+    assert_eq!(people.len(), 1);
+    assert_eq!(split.len(), 2);
+    assert_eq!(people.select().by_last_name("Zhuravleva").count(), 0);
+    assert_eq!(split.select().by_last_name("Zhuravleva").count(), 2);
+    assert_eq!(split.select().adults().count(), 1);
+
+    split.absorb(people);
+    assert_eq!(split.len(), 3);
+    let ids: Vec<_> = split
+        .select()
+        .by_last_name("Voronov")
+        .iter()
+        .map(|p| p.id)
+        .collect();
+    assert_eq!(ids, vec![1]);
+}
+
+#[test]
+fn clips_stale_rows_after_splitting() {
+    let mut people = people();
+    let everyone = people.select().selection().clone();
+    let last = people.indexer().by_id.get(&3).unwrap();
+    let voronovs = people.indexer().by_last_name.get("Voronov").clone();
+    people.split_off(&voronovs);
+
+    assert_eq!(people.select().only(everyone.clone()).count(), 2);
+    assert_eq!(people.select().none().or(&everyone).count(), 2);
+    assert_eq!(people.select().only_row(last).count(), 0);
+    assert!(people.select().only_row(last).first().is_none());
+}
+
+#[test]
+fn can_keep_row_state_when_splitting() {
+    let mut people = people()
+        .with_access_stats()
+        .with_eviction(3, Eviction::LeastRecentlyUsed);
+    people.select().by_id(3).first();
+    people.select().by_id(3).first();
+    people.select().by_id(2).first();
+
+    let voronovs = people.indexer().by_last_name.get("Voronov").clone();
+    people.split_off(&voronovs);
+    let reads: Vec<_> = people
+        .access_stats()
+        .unwrap()
+        .into_iter()
+        .map(|(_, stats)| stats.reads)
+        .collect();
+    assert_eq!(reads, vec![1, 2]);
+
+    for id in 4..6 {
+        people.insert(Person {
+            id,
+            first_name: "Ivan".to_string(),
+            last_name: "Voronov".to_string(),
+            age: 0,
+        });
+    }
+    assert!(people.indexer().by_id.get(&2).is_some());
+    assert!(people.indexer().by_id.get(&3).is_none());
+}
+
+#[test]
+fn can_estimate_query_cost() {
+    let people = people();
+    let adults = people.select().adults().estimated_rows();
+    assert_eq!(adults, 2);
+
+    let cost = people.select().by_last_name("Zhuravleva").estimated_cost();
+    assert_eq!(cost, 2 * std::mem::size_of::<Person>() as u64);
+    assert_eq!(people.select().none().estimated_cost(), 0);
+}
+
+#[test]
+fn can_keep_insertion_order_across_slot_reuse() {
+    let mut readings = readings().with_eviction(4, Eviction::Oldest);
+    readings.insert(Reading { at: at(140) });
+    readings.insert(Reading { at: at(150) });
+    readings
+        .update()
+        .apply(|reading| reading.at += Duration::from_secs(1));
+
+    let arrival = &readings.indexer().arrival;
+    let times: Vec<_> = arrival.rows().map(|row| readings[row].at).collect();
+    assert_eq!(times, vec![at(91), at(131), at(141), at(151)]);
+    assert_eq!(arrival.sequence(arrival.rows().next().unwrap()), Some(2));
+
+    let recent = readings
+        .indexer()
+        .last_minute
+        .in_window(at(151), Duration::from_secs(20));
+    let recent: Vec<_> = arrival
+        .ordered(&recent)
+        .into_iter()
+        .map(|row| readings[row].at)
+        .collect();
+    assert_eq!(recent, vec![at(141), at(151)]);
+}
+
+#[test]
+fn can_sort_with_custom_comparators() {
+    let people = people();
+    let mut by_first_name = ComparatorIndex::new(
+        |p: &Person| p.first_name.clone(),
+        |a: &String, b: &String| a.to_lowercase().cmp(&b.to_lowercase()),
+    );
+    let everyone = people.select();
+    for (row, person) in everyone.selection().rows().zip(everyone.iter_ref()) {
+        by_first_name.add(row, person);
+    }
+
+    let ids: Vec<_> = by_first_name.rows().map(|row| people[row].id).collect();
+    assert_eq!(ids, vec![1, 3, 2]);
+    assert_eq!(by_first_name.get(&"POLINA".to_string()).len(), 1);
+
+    let from_o = by_first_name.between(&"o".to_string(), &"pz".to_string());
+    assert_eq!(from_o.len(), 2);
+}
+
+#[test]
+fn can_normalize_string_keys() {
+    let people = people();
+    let mut by_first_name = PrefixIndex::normalized(|p: &Person| &p.first_name, str::to_lowercase);
+    let mut by_last_name = SuffixIndex::normalized(|p: &Person| &p.last_name, str::to_uppercase);
+    let everyone = people.select();
+    for (row, person) in everyone.selection().rows().zip(everyone.iter_ref()) {
+        by_first_name.add(row, person);
+        by_last_name.add(row, person);
+    }
+
+    assert_eq!(by_first_name.starts_with("OLI").len(), 1);
+    assert_eq!(by_first_name.starts_with("ale").len(), 1);
+    assert_eq!(by_last_name.ends_with("leva").len(), 2);
+    assert_eq!(
+        people
+            .select()
+            .starts_with(&people.indexer().by_first_name_prefix, "oli")
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn can_count_facets() {
+    let people = people();
+    let by_last_name = &people.indexer().by_last_name;
+    let (zhuravleva, voronov) = ("Zhuravleva".to_string(), "Voronov".to_string());
+
+    let everyone = people.select().facet(by_last_name);
+    assert_eq!(everyone.len(), 2);
+    assert_eq!(everyone[&zhuravleva], 2);
+    assert_eq!(everyone[&voronov], 1);
+
+    let adults = people.select().adults().facet(by_last_name);
+    assert_eq!(adults[&zhuravleva], 1);
+
+    let children = people.select().adults().invert().facet(by_last_name);
+    assert_eq!(children.len(), 1);
+    assert!(!children.contains_key(&voronov));
+}
+
+#[test]
+fn can_suggest_completions() {
+    let mut people = people();
+    people.insert(Person {
+        id: 4,
+        first_name: "Olga".to_string(),
+        last_name: "Voronova".to_string(),
+        age: 54,
+    });
+    people.insert(Person {
+        id: 5,
+        first_name: "Olga".to_string(),
+        last_name: "Zhuravleva".to_string(),
+        age: 81,
+    });
+
+    let by_first_name = &people.indexer().by_first_name_prefix;
+    assert_eq!(
+        by_first_name.suggest("O", 5),
+        vec![("Olga", 2), ("Olivia Alekseevna", 1)]
+    );
+    assert_eq!(by_first_name.suggest("O", 1), vec![("Olga", 2)]);
+    assert!(by_first_name.suggest("X", 3).is_empty());
+
+    let longest = by_first_name.suggest_weighted("", 2, |key, _| key.len());
+    assert_eq!(longest, vec![("Olivia Alekseevna", 17), ("Aleksei", 7)]);
+
+    people
+        .update()
+        .by_id(3)
+        .apply(|p| p.first_name = "Olga".to_string());
+    let by_first_name = &people.indexer().by_first_name_prefix;
+    assert_eq!(by_first_name.suggest("O", 5), vec![("Olga", 3)]);
+    assert_eq!(by_first_name.stats(), IndexStats { keys: 3, rows: 5 });
+}
+
+#[test]
+fn can_query_validity_intervals() {
+    let mut prices: Table<Price> = vec![
+        Price {
+            sku: "tea",
+            cents: 300,
+            valid_from: 10,
+            valid_to: None,
+        },
+        Price {
+            sku: "coffee",
+            cents: 450,
+            valid_from: 0,
+            valid_to: Some(20),
+        },
+    ]
+    .into_iter()
+    .collect();
+
+    let cents_at = |prices: &Table<Price>, at: u32| {
+        let mut cents: Vec<_> = prices
+            .select()
+            .valid_at(&prices.indexer().validity, &at)
+            .iter()
+            .map(|price| price.cents)
+            .collect();
+        cents.sort_unstable();
+        cents
+    };
+    assert_eq!(cents_at(&prices, 5), vec![450]);
+    assert_eq!(cents_at(&prices, 10), vec![300, 450]);
+    assert_eq!(cents_at(&prices, 20), vec![300]);
+
+    prices.update().apply_if(
+        |price| price.sku == "tea" && price.valid_to.is_none(),
+        |price| price.valid_to = Some(30),
+    );
+    prices.insert(Price {
+        sku: "tea",
+        cents: 350,
+        valid_from: 30,
+        valid_to: None,
+    });
+
+    assert_eq!(cents_at(&prices, 29), vec![300]);
+    assert_eq!(cents_at(&prices, 30), vec![350]);
+    assert_eq!(cents_at(&prices, 5), vec![450]);
+}
+
+#[test]
+fn can_replay_events() {
+    let joined = |id, first_name: &str, age| {
+        PersonEvent::Joined(Person {
+            id,
+            first_name: first_name.to_string(),
+            last_name: "Zhuravleva".to_string(),
+            age,
+        })
+    };
+
+    let mut people = Table::in_memory();
+    people.replay(vec![
+        joined(1, "Olga", 29),
+        joined(2, "Vera", 31),
+        PersonEvent::HadBirthday { id: 1 },
+    ]);
+    assert_eq!(people.select().by_last_name("Zhuravleva").count(), 2);
+
+    people.apply_event(PersonEvent::Married {
+        id: 2,
+        last_name: "Voronova",
+    });
+    people.apply_event(PersonEvent::HadBirthday { id: 3 });
+
+    let ages: Vec<_> = people
+        .select()
+        .by_last_name("Zhuravleva")
+        .iter()
+        .map(|person| person.age)
+        .collect();
+    assert_eq!(ages, vec![30]);
+    assert_eq!(people.select().by_last_name("Voronova").count(), 1);
+    assert_eq!(people.len(), 2);
+}
+
+#[test]
+fn can_consume_selections() {
+    let people = people();
+    let mut queue = people.select().selection().clone();
+
+    let first = queue.pop_min().unwrap();
+    let last = queue.pop_max().unwrap();
+    assert!(first < last);
+    assert!(!queue.contains(first) && !queue.contains(last));
+
+    let rest: Vec<_> = queue.drain_rows().take(1).collect();
+    assert_eq!(rest.len(), 1);
+    assert!(!queue.contains(rest[0]));
+    assert_eq!(queue.len(), people.len() as u64 - 3);
+
+    let rest: Vec<_> = queue.drain_rows().collect();
+    assert!(rest.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(queue.is_empty());
+    assert_eq!(queue.pop_min(), None);
+    assert_eq!(queue.pop_max(), None);
+}
+
+#[test]
+fn can_compare_queries() {
+    let people = people();
+    let mut adults = people.select();
+    adults.adults();
+    let mut voronovs = people.select();
+    voronovs.by_last_name("Voronov");
+    let mut zhuravlevas = people.select();
+    zhuravlevas.by_last_name("Zhuravleva");
+
+    assert!(adults.overlaps(&zhuravlevas));
+    assert!(voronovs.is_disjoint_with(&zhuravlevas));
+    assert!(!voronovs.overlaps(&zhuravlevas));
+
+    assert!(voronovs.is_subset_of(&adults));
+    assert!(!zhuravlevas.is_subset_of(&adults));
+    assert!(adults.is_subset_of(&people.select()));
+}
+
+#[test]
+fn can_select_complement_of_boolean_index() {
+    let mut people = people();
+    let minors = |people: &Table<Person>| -> Vec<u32> {
+        let minors = people.indexer().adults.get_false().clone();
+        people.select().and(&minors).iter().map(|p| p.id).collect()
+    };
+    assert_eq!(minors(&people), vec![3]);
+
+    people
+        .update()
+        .by_last_name("Voronov")
+        .apply(|person| person.age = 17);
+    assert_eq!(minors(&people), vec![1, 3]);
+
+    people.update().apply_batched(|person| person.age += 1);
+    assert_eq!(minors(&people), vec![3]);
+    assert_eq!(
+        people.indexer().adults.get().len() + people.indexer().adults.get_false().len(),
+        people.len() as u64
+    );
+}
+
+#[test]
+fn can_add_and_remove_discrete_rows() {
+    let people = people();
+    let zhuravlevas: Vec<_> = people.select().by_last_name("Zhuravleva").iter().collect();
+    let row = |index| unsafe { Row::from_index(index) };
+
+    let mut by_last_name = DiscreteIndex::new(|p: &Person| &p.last_name);
+    for index in 0..3 {
+        by_last_name.add(row(index), &zhuravlevas[index % 2]);
+    }
+    assert_eq!(by_last_name.get("Zhuravleva").len(), 3);
+
+    by_last_name.remove(row(1), &zhuravlevas[1]);
+    let rows: Vec<_> = by_last_name.get("Zhuravleva").rows().collect();
+    assert_eq!(rows, vec![row(0), row(2)]);
+
+    by_last_name.remove(row(0), &zhuravlevas[0]);
+    by_last_name.add(row(2), &zhuravlevas[0]);
+    assert_eq!(by_last_name.get("Zhuravleva").len(), 1);
+
+    by_last_name.remove(row(2), &zhuravlevas[0]);
+    assert!(by_last_name.get("Zhuravleva").is_empty());
+    assert_eq!(by_last_name.stats(), IndexStats { keys: 0, rows: 0 });
+}
+
+#[test]
+fn can_enumerate_discrete_keys() {
+    let mut people = people();
+    let mut keys: Vec<_> = people.indexer().by_last_name.keys().cloned().collect();
+    keys.sort();
+    assert_eq!(keys, vec!["Voronov", "Zhuravleva"]);
+    assert_eq!(people.indexer().by_last_name.len(), 2);
+
+    let counts: Vec<_> = people
+        .indexer()
+        .by_last_name
+        .iter()
+        .map(|(key, selection)| (key.clone(), selection.len()))
+        .filter(|(key, _)| key == "Zhuravleva")
+        .collect();
+    assert_eq!(counts, vec![("Zhuravleva".to_string(), 2)]);
+
+    people
+        .update()
+        .by_last_name("Voronov")
+        .apply(|person| person.last_name = "Zhuravleva".to_string());
+    let keys: Vec<_> = people.indexer().by_last_name.keys().collect();
+    assert_eq!(keys, vec!["Zhuravleva"]);
+    assert!(!people.indexer().by_last_name.is_empty());
+}
+
+#[test]
+fn can_enumerate_unique_keys() {
+    let mut people = people();
+    let by_id = &people.indexer().by_id;
+    let mut ids: Vec<_> = by_id.keys().copied().collect();
+    ids.sort_unstable();
+    assert_eq!(ids, vec![1, 2, 3]);
+    assert_eq!(by_id.len(), 3);
+    assert!(by_id.contains(&2));
+    assert!(!by_id.contains(&4));
+
+    people
+        .update()
+        .by_last_name("Voronov")
+        .apply(|person| person.id = 4);
+    let by_id = &people.indexer().by_id;
+    assert!(by_id.contains(&4));
+    assert!(!by_id.contains(&1));
+    assert_eq!(by_id.len(), 3);
+}
+
+#[test]
+fn can_enforce_compound_unique_keys() {
+    let account = |tenant, email: &str| Account {
+        tenant,
+        email: email.to_string(),
+    };
+    let mut accounts = Table::in_memory();
+    let olga = accounts
+        .insert_unique(account(1, "olga@example.com"))
+        .unwrap();
+    accounts
+        .insert_unique(account(2, "olga@example.com"))
+        .unwrap();
+    accounts
+        .insert_unique(account(1, "vera@example.com"))
+        .unwrap();
+
+    let conflict = accounts
+        .insert_unique(account(1, "olga@example.com"))
+        .unwrap_err();
+    assert_eq!(conflict.row, olga);
+    assert_eq!(accounts.len(), 3);
+
+    let key = (1, "olga@example.com".to_string());
+    assert_eq!(accounts.indexer().by_tenant_email.get(&key), Some(olga));
+}
+
+#[test]
+fn can_select_by_derived_key() {
+    let mut people = people();
+    let full_name = |people: &Table<Person>, name: &str| -> Vec<u32> {
+        let selection = people.indexer().by_full_name.get(name).clone();
+        people
+            .select()
+            .and(&selection)
+            .iter()
+            .map(|p| p.id)
+            .collect()
+    };
+    assert_eq!(full_name(&people, "Polina Zhuravleva"), vec![2]);
+
+    people
+        .update()
+        .by_id(2)
+        .apply(|person| person.last_name = "Voronova".to_string());
+    assert!(full_name(&people, "Polina Zhuravleva").is_empty());
+    assert_eq!(full_name(&people, "Polina Voronova"), vec![2]);
+
+    people.update().by_id(2).apply(|person| person.age += 1);
+    assert_eq!(full_name(&people, "Polina Voronova"), vec![2]);
+    assert_eq!(
+        people.indexer().by_full_name.stats(),
+        IndexStats { keys: 3, rows: 3 }
+    );
+}
+
+#[test]
+fn can_join_selections() {
+    let people = people();
+    let documents: Table<Arc<Document>> = ["Zhuravleva", "Ivanova", "Zhuravleva"]
+        .iter()
+        .enumerate()
+        .map(|(id, title)| {
+            Arc::new(Document {
+                id: id as u32,
+                title: title.to_string(),
+            })
+        })
+        .collect();
+
+    let mut pairs: Vec<_> = join_selections(
+        &people.indexer().by_last_name,
+        &documents.indexer().by_title,
+    )
+    .map(|(person, document)| (people[person].id, documents[document].id))
+    .collect();
+    pairs.sort_unstable();
+    assert_eq!(pairs, vec![(2, 0), (2, 2), (3, 0), (3, 2)]);
+}
+
+#[test]
+fn can_fill_selections_across_containers() {
+    let row = |index| unsafe { Row::<Person>::from_index(index) };
+    let filled = Selection::filled(70_000);
+    assert_eq!(filled.len(), 70_000);
+    assert!(filled.contains(row(0)));
+    assert!(filled.contains(row(69_999)));
+    assert!(!filled.contains(row(70_000)));
+    assert!(Selection::<Person>::filled(0).is_empty());
+}
+
+#[test]
+fn can_warm_indexes() {
+    let mut people = people();
+    people.extend((4..1000).map(|id| Person {
+        id,
+        first_name: "Olga".to_string(),
+        last_name: "Zhuravleva".to_string(),
+        age: (id % 90) as u8,
+    }));
+    let stats = people.indexer().stats();
+    let adults = people.select().adults().count();
+
+    people.warm();
+
+    assert_eq!(people.indexer().stats(), stats);
+    assert_eq!(people.select().adults().count(), adults);
+    assert_eq!(people.select().by_last_name("Zhuravleva").count(), 998);
+}
+
+#[test]
+fn can_move_only_changed_keys_on_update() {
+    let mut people = people();
+    people.update().by_id(3).apply(|person| {
+        person.id = 4;
+        person.age = 40;
+    });
+
+    let indexer = people.indexer();
+    assert_eq!(indexer.by_id.get(&3), None);
+    assert_eq!(indexer.by_id.get(&4).map(|row| people[row].age), Some(40));
+    assert_eq!(indexer.by_last_name.get("Zhuravleva").len(), 2);
+    assert_eq!(indexer.by_age.get(&0).len(), 0);
+    assert_eq!(indexer.age_range.min(), Some(&28));
+    assert_eq!(indexer.age_range.max(), Some(&40));
+    assert_eq!(indexer.seniority.highest(), indexer.by_id.get(&4));
+    assert_eq!(indexer.adults.get().len(), 3);
+
+    let mut prices: Table<Price> = vec![Price {
+        sku: "tea",
+        cents: 300,
+        valid_from: 10,
+        valid_to: None,
+    }]
+    .into_iter()
+    .collect();
+    prices.update().apply(|price| price.valid_to = Some(20));
+    let validity = &prices.indexer().validity;
+    assert_eq!(validity.valid_at(&15).len(), 1);
+    assert_eq!(validity.valid_at(&20).len(), 0);
+}
+
 #[test]
 fn can_use_custom_mut_methods() {
     let mut people = people();